@@ -0,0 +1,337 @@
+use crate::error::{Error, Result};
+use crate::ortho::{MultiSet, Ortho};
+use crate::repo::{Repo, RepoStore};
+use crate::Config;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use string_interner::StringInterner;
+use string_interner::Symbol;
+
+const MAGIC: &[u8; 4] = b"MFLD";
+const VERSION: u32 = 1;
+
+// magic, version, then varint-prefixed sections:
+// the symbol table in id order, forward edges by source each followed by its
+// count, orthos as cell lists.
+// word ids in the file are interner symbols, so they survive a reload unchanged.
+pub fn save(
+    interner: &StringInterner,
+    config: &Config,
+    repo: &Repo,
+    mut target: impl Write,
+) -> Result<()> {
+    let mut buffer = vec![];
+    buffer.extend_from_slice(MAGIC);
+    buffer.extend_from_slice(&VERSION.to_le_bytes());
+
+    write_varint(&mut buffer, interner.len());
+    for (_symbol, word) in interner {
+        write_varint(&mut buffer, word.len());
+        buffer.extend_from_slice(word.as_bytes());
+    }
+
+    let mut edges: BTreeMap<usize, BTreeMap<usize, usize>> = BTreeMap::default();
+    for (from, to) in config.edges() {
+        edges
+            .entry(from)
+            .or_default()
+            .insert(to, config.count(from, to));
+    }
+    write_varint(&mut buffer, edges.len());
+    for (from, to) in edges {
+        write_varint(&mut buffer, from);
+        write_varint(&mut buffer, to.len());
+        for (to, count) in to {
+            write_varint(&mut buffer, to);
+            write_varint(&mut buffer, count);
+        }
+    }
+
+    let orthos: BTreeSet<&Ortho> = repo.iter().collect();
+    write_varint(&mut buffer, orthos.len());
+    for ortho in orthos {
+        encode(&mut buffer, ortho);
+    }
+
+    target.write_all(&buffer)?;
+    Ok(())
+}
+
+pub fn load(mut source: impl Read) -> Result<(StringInterner, Config, Repo)> {
+    let mut contents = vec![];
+    source.read_to_end(&mut contents)?;
+    let mut reader = Reader::new(&contents);
+
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(Error::Format("not a model-fold state file".to_string()));
+    }
+    let version = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+    if version != VERSION {
+        return Err(Error::Format(format!("unsupported version {}", version)));
+    }
+
+    let mut interner = StringInterner::default();
+    for id in 0..reader.varint()? {
+        let length = reader.varint()?;
+        let word = std::str::from_utf8(reader.take(length)?)
+            .map_err(|_| Error::Format("word is not utf-8".to_string()))?;
+        if interner.get_or_intern(word).to_usize() != id {
+            return Err(Error::Format(format!("duplicate word {}", word)));
+        }
+    }
+    let words = interner.len();
+    let symbol = |id: usize| {
+        if id < words {
+            Ok(id)
+        } else {
+            Err(Error::Format(format!("word id {} out of range", id)))
+        }
+    };
+
+    let mut config = Config::new();
+    for _ in 0..reader.varint()? {
+        let from = symbol(reader.varint()?)?;
+        for _ in 0..reader.varint()? {
+            let to = symbol(reader.varint()?)?;
+            config.set_count(from, to, reader.varint()?);
+        }
+    }
+
+    let mut repo = Repo::new();
+    for _ in 0..reader.varint()? {
+        repo.add(decode(&mut reader, symbol)?)?;
+    }
+
+    if !reader.is_empty() {
+        return Err(Error::Format("trailing bytes".to_string()));
+    }
+    Ok((interner, config, repo))
+}
+
+pub fn encode(buffer: &mut Vec<u8>, ortho: &Ortho) {
+    write_varint(buffer, ortho.cells().count());
+    for (location, word) in ortho.cells() {
+        write_varint(buffer, location.items().count());
+        for axis in location.items() {
+            write_varint(buffer, *axis);
+            write_varint(buffer, location.count(*axis));
+        }
+        write_varint(buffer, *word);
+    }
+}
+
+pub fn decode(reader: &mut Reader, symbol: impl Fn(usize) -> Result<usize>) -> Result<Ortho> {
+    let mut cells = BTreeMap::default();
+    let count = reader.varint()?;
+    // every cell takes at least two bytes, and an axis repeated n times
+    // needs n + 1 cells, so both are bounded by the input
+    if count > reader.remaining() {
+        return Err(Error::Format(format!("{} cells out of range", count)));
+    }
+    for _ in 0..count {
+        let mut location = MultiSet::new();
+        for _ in 0..reader.varint()? {
+            let axis = symbol(reader.varint()?)?;
+            let multiplicity = reader.varint()?;
+            if multiplicity == 0 || multiplicity >= count {
+                return Err(Error::Format(format!(
+                    "multiplicity {} out of range",
+                    multiplicity
+                )));
+            }
+            location.insert_many(axis, multiplicity);
+        }
+        cells.insert(location, symbol(reader.varint()?)?);
+    }
+    Ortho::from_locations(cells)
+        .ok_or_else(|| Error::InconsistentRepo("malformed ortho".to_string()))
+}
+
+pub fn write_varint(buffer: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+pub struct Reader<'a> {
+    contents: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(contents: &'a [u8]) -> Reader<'a> {
+        Reader {
+            contents,
+            position: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.contents.len()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.contents.len() - self.position
+    }
+
+    pub fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.contents.len())
+            .ok_or_else(|| Error::Format("unexpected end of file".to_string()))?;
+        let bytes = &self.contents[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn varint(&mut self) -> Result<usize> {
+        let mut value = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::Format("varint too long".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Support;
+    use crate::search::make_atoms;
+
+    fn folded(text: &str) -> (StringInterner, Config, Repo) {
+        let (config, interner) = Config::from_sentences(text.to_string());
+        let mut repo = Repo::new();
+        make_atoms(&config, Support::default(), &mut repo, 1).unwrap();
+        (interner, config, repo)
+    }
+
+    #[test]
+    fn it_round_trips() {
+        let (interner, config, repo) = folded("a b e. c d f. a c. b d. e f. g h. e g. f h.");
+        let mut buffer = vec![];
+        save(&interner, &config, &repo, &mut buffer).unwrap();
+
+        let (loaded_interner, loaded_config, loaded_repo) = load(buffer.as_slice()).unwrap();
+        assert_eq!(
+            loaded_repo.unintern(&loaded_interner).unwrap(),
+            repo.unintern(&interner).unwrap()
+        );
+        assert_eq!(
+            loaded_config.unintern(&loaded_interner).unwrap(),
+            config.unintern(&interner).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_is_smaller_than_yaml() {
+        let (interner, config, repo) = folded("a b e. c d f. a c. b d. e f.");
+        let mut buffer = vec![];
+        save(&interner, &config, &repo, &mut buffer).unwrap();
+
+        let mut yaml = vec![];
+        repo.unintern(&interner).unwrap().save(&mut yaml).unwrap();
+        config.unintern(&interner).unwrap().save(&mut yaml).unwrap();
+        assert!(buffer.len() * 4 < yaml.len());
+    }
+
+    #[test]
+    fn it_writes_the_same_bytes_for_the_same_state() {
+        let (interner, config, repo) = folded("a b. c d. a c. b d.");
+        let mut first = vec![];
+        save(&interner, &config, &repo, &mut first).unwrap();
+
+        let (loaded_interner, loaded_config, loaded_repo) = load(first.as_slice()).unwrap();
+        let mut second = vec![];
+        save(&loaded_interner, &loaded_config, &loaded_repo, &mut second).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn it_keeps_symbol_ids() {
+        let (interner, config, repo) = folded("z y. x w. z x. y w.");
+        let mut buffer = vec![];
+        save(&interner, &config, &repo, &mut buffer).unwrap();
+
+        let (loaded_interner, _loaded_config, loaded_repo) = load(buffer.as_slice()).unwrap();
+        for (symbol, word) in &interner {
+            assert_eq!(loaded_interner.get(word), Some(symbol));
+        }
+        assert_eq!(loaded_repo, repo);
+    }
+
+    #[test]
+    fn it_keeps_edge_counts() {
+        let (interner, config, repo) = folded("a b. a b. a b c. c d. a c. b d.");
+        let mut buffer = vec![];
+        save(&interner, &config, &repo, &mut buffer).unwrap();
+
+        let (loaded_interner, loaded_config, _loaded_repo) = load(buffer.as_slice()).unwrap();
+        let id = |word| loaded_interner.get(word).unwrap().to_usize();
+        assert_eq!(loaded_config.count(id("a"), id("b")), 3);
+        assert_eq!(loaded_config.count(id("b"), id("c")), 1);
+        assert_eq!(
+            loaded_config.unintern(&loaded_interner).unwrap(),
+            config.unintern(&interner).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_rejects_bad_files() {
+        let (interner, config, repo) = folded("a b. c d. a c. b d.");
+        let mut buffer = vec![];
+        save(&interner, &config, &repo, &mut buffer).unwrap();
+
+        assert!(matches!(load(&b"nope"[..]), Err(Error::Format(_))));
+        assert!(matches!(
+            load(&buffer[..buffer.len() - 1]),
+            Err(Error::Format(_))
+        ));
+
+        let mut newer = buffer.clone();
+        newer[4] = 99;
+        assert!(matches!(load(newer.as_slice()), Err(Error::Format(_))));
+
+        buffer.push(0);
+        assert!(matches!(load(buffer.as_slice()), Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn it_rejects_corrupt_multiplicities() {
+        // four cells, the first one at axis 1 repeated a huge number of times
+        let mut buffer = vec![4, 1, 1];
+        write_varint(&mut buffer, usize::MAX);
+        buffer.extend_from_slice(&[0, 0, 1, 2, 1, 1, 1, 2, 1, 3, 2, 1, 1, 2, 1, 4]);
+        assert!(matches!(
+            decode(&mut Reader::new(&buffer), Ok),
+            Err(Error::Format(_))
+        ));
+
+        let mut buffer = vec![];
+        write_varint(&mut buffer, usize::MAX);
+        buffer.extend_from_slice(&[0, 0]);
+        assert!(matches!(
+            decode(&mut Reader::new(&buffer), Ok),
+            Err(Error::Format(_))
+        ));
+
+        let square = [4, 0, 0, 1, 1, 1, 1, 1, 2, 1, 2, 2, 1, 1, 2, 1, 3];
+        assert_eq!(
+            decode(&mut Reader::new(&square), Ok).unwrap(),
+            Ortho::new(0, 1, 2, 3)
+        );
+    }
+}
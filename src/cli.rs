@@ -0,0 +1,537 @@
+use model_fold::{Backend, Engine, Format, LockMode, Query, Support, Workspace};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str =
+    "usage: model-fold [--dir DIR] [--config FILE] [--repo FILE] [--state FILE] [--threads N] [--no-wait]
+                  [--tokenizer classic|lines|unicode[:OPTIONS]|prose[:OPTIONS]]
+                  [--abbreviations FILE] [--min-support N|F|P%] [--store memory|sled] <command>
+
+commands:
+  ingest <files...>                          fold each file into the workspace
+  query [--origin W] [--hop W] [--size AxB]  print matching orthos
+        [--contains W]... [--at W@I,J]       with every W in them, or W at row I, column J
+  show <word>                                print every ortho holding the word as a grid
+  dot [--around W] [--hops K]                print the word graph as graphviz, edges inside
+                                             orthos in red; --around keeps words within K
+                                             steps of W (default 1)
+  stats                                      print repo statistics
+  export [--format yaml|jsonl|csv]           print the repo; jsonl has one ortho per line,
+                                             csv one a,b,c,d row per 2x2
+  rescan                                     search the whole word graph again
+  convert [--force]                          rewrite the yaml config and repo as the binary state file;
+                                             --force replaces existing binary state and journal
+  compact                                    fold the journal into the binary state file
+  reset                                      delete the workspace state
+  help                                       print this message
+
+--at counts from 0 along the axes shortest first, so W@I,J is row I, column J as show
+draws the ortho; further axes come first and missing coordinates are 0
+--no-wait fails straight away instead of waiting when another run holds the workspace
+--tokenizer picks how ingest reads text: classic splits sentences on . ! ? and lowercases,
+lines takes one sentence per line with tokens verbatim, unicode follows unicode word and
+sentence boundaries; its options are nfc (default), nfkc, no-normalization, fold-case
+(default) and keep-case, e.g. unicode:nfkc,keep-case; prose splits words the same way but
+keeps abbreviations, decimals, ellipses and quoted ends inside their sentence
+--abbreviations replaces the abbreviations prose knows with the words in FILE, one a line
+--min-support keeps edges seen fewer than N times, or making up less than a fraction F
+(or P percent) of what follows their first word, out of the search; their counts are kept,
+so rescan after lowering it finds what was held back
+--store sled moves the orthos out of the state file into a sled database beside it, so
+ingest no longer holds them all in memory; later runs use it without being asked";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Ingest(Vec<PathBuf>),
+    Query(Query),
+    Show(String),
+    Dot(Option<(String, usize)>),
+    Stats,
+    Export(Format),
+    Convert { force: bool },
+    Compact,
+    Rescan,
+    Reset,
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Invocation {
+    pub workspace: Workspace,
+    pub threads: Option<usize>,
+    pub lock: LockMode,
+    pub tokenizer: Option<String>,
+    pub abbreviations: Option<PathBuf>,
+    pub support: Option<Support>,
+    pub store: Option<Backend>,
+    pub command: Command,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Invocation, String> {
+    let mut args = args.into_iter();
+    let mut dir = None;
+    let mut config = None;
+    let mut repo = None;
+    let mut state = None;
+    let mut threads = None;
+    let mut lock = LockMode::Wait;
+    let mut tokenizer = None;
+    let mut abbreviations = None;
+    let mut support = None;
+    let mut store = None;
+    let mut query = Query::default();
+    let mut format = None;
+    let mut around = None;
+    let mut hops = None;
+    let mut force = false;
+    let mut command = None;
+    let mut positional = vec![];
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => command = Some("help".to_string()),
+            "--dir" => dir = Some(value(&mut args, &arg)?),
+            "--config" => config = Some(value(&mut args, &arg)?),
+            "--repo" => repo = Some(value(&mut args, &arg)?),
+            "--state" => state = Some(value(&mut args, &arg)?),
+            "--threads" => threads = Some(count(&value(&mut args, &arg)?)?),
+            "--no-wait" => lock = LockMode::Fail,
+            "--tokenizer" => tokenizer = Some(value(&mut args, &arg)?),
+            "--abbreviations" => abbreviations = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--min-support" => {
+                support = Some(
+                    value(&mut args, &arg)?
+                        .parse()
+                        .map_err(|e: model_fold::Error| e.to_string())?,
+                )
+            }
+            "--store" => {
+                store = Some(
+                    value(&mut args, &arg)?
+                        .parse()
+                        .map_err(|e: model_fold::Error| e.to_string())?,
+                )
+            }
+            "--origin" => query.origin = Some(value(&mut args, &arg)?),
+            "--hop" => query.hop = Some(value(&mut args, &arg)?),
+            "--size" => query.shape = Some(shape(&value(&mut args, &arg)?)?),
+            "--contains" => query.contains.push(value(&mut args, &arg)?),
+            "--at" => query.at = Some(position(&value(&mut args, &arg)?)?),
+            "--around" => around = Some(value(&mut args, &arg)?),
+            "--hops" => hops = Some(steps(&value(&mut args, &arg)?)?),
+            "--force" => force = true,
+            "--format" => {
+                format = Some(
+                    value(&mut args, &arg)?
+                        .parse()
+                        .map_err(|e: model_fold::Error| e.to_string())?,
+                )
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            _ if command.is_none() => command = Some(arg),
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut workspace = dir.map(Workspace::in_dir).unwrap_or_default();
+    if let Some(config) = config {
+        workspace.config = config.into();
+    }
+    if let Some(repo) = repo {
+        workspace.repo = repo.into();
+    }
+    if let Some(state) = state {
+        workspace.state = state.into();
+    }
+
+    let command = match command.as_deref() {
+        None => return Err("missing command".to_string()),
+        Some("help") => Command::Help,
+        Some("ingest") if positional.is_empty() => {
+            return Err("ingest needs at least one file".to_string())
+        }
+        Some("ingest") => Command::Ingest(positional.drain(..).map(PathBuf::from).collect()),
+        Some("query") => Command::Query(query.clone()),
+        Some("show") if positional.len() != 1 => {
+            return Err("show needs exactly one word".to_string())
+        }
+        Some("show") => Command::Show(positional.remove(0)),
+        Some("dot") if hops.is_some() && around.is_none() => {
+            return Err("--hops needs --around".to_string())
+        }
+        Some("dot") => Command::Dot(around.take().map(|word| (word, hops.take().unwrap_or(1)))),
+        Some("stats") => Command::Stats,
+        Some("export") => Command::Export(format.take().unwrap_or(Format::Yaml)),
+        Some("convert") => Command::Convert {
+            force: std::mem::take(&mut force),
+        },
+        Some("compact") => Command::Compact,
+        Some("rescan") => Command::Rescan,
+        Some("reset") => Command::Reset,
+        Some(other) => return Err(format!("unknown command {}", other)),
+    };
+
+    if !positional.is_empty() {
+        return Err(format!("unexpected argument {}", positional[0]));
+    }
+    if command != Command::Query(query.clone()) && query != Query::default() {
+        return Err("--origin, --hop, --size, --contains and --at only apply to query".to_string());
+    }
+    if format.is_some() {
+        return Err("--format only applies to export".to_string());
+    }
+    if around.is_some() || hops.is_some() {
+        return Err("--around and --hops only apply to dot".to_string());
+    }
+    if force {
+        return Err("--force only applies to convert".to_string());
+    }
+    let ingests = matches!(command, Command::Ingest(_));
+    if !ingests && (tokenizer.is_some() || abbreviations.is_some()) {
+        return Err("--tokenizer and --abbreviations only apply to ingest".to_string());
+    }
+    if !ingests && store.is_some() {
+        return Err("--store only applies to ingest".to_string());
+    }
+    if !ingests && command != Command::Rescan && (threads.is_some() || support.is_some()) {
+        return Err("--threads and --min-support only apply to ingest and rescan".to_string());
+    }
+    match &tokenizer {
+        Some(name) => {
+            model_fold::tokenizer(name, abbreviations.as_ref().map(|_| vec![]))
+                .map_err(|e| e.to_string())?;
+        }
+        None if abbreviations.is_some() => {
+            return Err("--abbreviations needs --tokenizer prose".to_string())
+        }
+        None => {}
+    }
+
+    Ok(Invocation {
+        workspace,
+        threads,
+        lock,
+        tokenizer,
+        abbreviations,
+        support,
+        store,
+        command,
+    })
+}
+
+pub fn run(invocation: Invocation) -> Result<(), String> {
+    let workspace = &invocation.workspace;
+    let lock = invocation.lock;
+    let open = || Engine::open_with(workspace.clone(), lock).map_err(|e| e.to_string());
+    let existing = || Engine::open_existing(workspace.clone(), lock).map_err(|e| e.to_string());
+    match invocation.command {
+        Command::Help => println!("{}", USAGE),
+        Command::Rescan => {
+            let mut engine = existing()?;
+            if let Some(threads) = invocation.threads {
+                engine.set_threads(threads);
+            }
+            if let Some(support) = invocation.support {
+                engine.set_support(support);
+            }
+            engine.rescan().map_err(|e| e.to_string())?;
+            engine.save().map_err(|e| e.to_string())?;
+        }
+        Command::Ingest(files) => {
+            let mut engine = open()?;
+            if let Some(threads) = invocation.threads {
+                engine.set_threads(threads);
+            }
+            if let Some(support) = invocation.support {
+                engine.set_support(support);
+            }
+            if let Some(store) = invocation.store {
+                engine.set_backend(store).map_err(|e| e.to_string())?;
+            }
+            if let Some(name) = &invocation.tokenizer {
+                let abbreviations = match &invocation.abbreviations {
+                    Some(file) => Some(lines(file)?),
+                    None => None,
+                };
+                engine.set_tokenizer(
+                    model_fold::tokenizer(name, abbreviations).map_err(|e| e.to_string())?,
+                );
+            }
+            for file in files {
+                let text =
+                    fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+                engine.ingest(text).map_err(|e| e.to_string())?;
+            }
+            engine.save().map_err(|e| e.to_string())?;
+        }
+        Command::Query(query) => {
+            let results = existing()?.query(&query).map_err(|e| e.to_string())?;
+            let yaml = serde_yaml::to_string(&results).map_err(|e| e.to_string())?;
+            write!(io::stdout().lock(), "{}", yaml).map_err(|e| e.to_string())?
+        }
+        Command::Show(word) => {
+            let grids = existing()?.show(&word).map_err(|e| e.to_string())?;
+            writeln!(io::stdout().lock(), "{}", grids.join("\n\n")).map_err(|e| e.to_string())?
+        }
+        Command::Dot(around) => existing()?
+            .dot(
+                around.as_ref().map(|(word, hops)| (word.as_str(), *hops)),
+                io::stdout().lock(),
+            )
+            .map_err(|e| e.to_string())?,
+        Command::Stats => {
+            let stats = existing()?.stats().map_err(|e| e.to_string())?;
+            write!(io::stdout().lock(), "{}", stats).map_err(|e| e.to_string())?
+        }
+        Command::Export(format) => existing()?
+            .export(format, io::stdout().lock())
+            .map_err(|e| e.to_string())?,
+        Command::Convert { force } => {
+            model_fold::convert(workspace, force).map_err(|e| e.to_string())?
+        }
+        Command::Compact => existing()?.compact().map_err(|e| e.to_string())?,
+        Command::Reset => {
+            if workspace.exists() {
+                let _lock = workspace.lock(lock).map_err(|e| e.to_string())?;
+                workspace.reset().map_err(|e| e.to_string())?
+            }
+        }
+    }
+    Ok(())
+}
+
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{} needs a value", flag))
+}
+
+fn count(spec: &str) -> Result<usize, String> {
+    match spec.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("invalid thread count {}", spec)),
+    }
+}
+
+fn lines(file: &Path) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect())
+}
+
+fn steps(spec: &str) -> Result<usize, String> {
+    spec.parse()
+        .map_err(|_| format!("invalid hop count {}", spec))
+}
+
+fn position(spec: &str) -> Result<(String, Vec<usize>), String> {
+    let (word, coordinates) = spec
+        .rsplit_once('@')
+        .ok_or_else(|| format!("invalid position {}", spec))?;
+    let coordinates = coordinates
+        .split(',')
+        .map(|c| c.parse().map_err(|_| format!("invalid position {}", spec)))
+        .collect::<Result<_, _>>()?;
+    Ok((word.to_string(), coordinates))
+}
+
+fn shape(spec: &str) -> Result<Vec<usize>, String> {
+    spec.split('x')
+        .map(|length| length.parse().map_err(|_| format!("invalid size {}", spec)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn it_parses_ingest() {
+        let invocation = parse(args("--dir state ingest a.txt b.txt")).unwrap();
+        assert_eq!(invocation.workspace, Workspace::in_dir("state"));
+        assert_eq!(invocation.threads, None);
+        assert_eq!(invocation.lock, LockMode::Wait);
+        assert_eq!(invocation.tokenizer, None);
+        assert_eq!(
+            invocation.command,
+            Command::Ingest(vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")])
+        );
+    }
+
+    #[test]
+    fn it_parses_query() {
+        let invocation = parse(args("query --origin a --size 2x3 --repo r.yaml")).unwrap();
+        assert_eq!(invocation.workspace.repo, PathBuf::from("r.yaml"));
+        assert_eq!(
+            invocation.command,
+            Command::Query(Query {
+                origin: Some("a".to_string()),
+                hop: None,
+                shape: Some(vec![2, 3]),
+                ..Query::default()
+            })
+        );
+    }
+
+    #[test]
+    fn it_parses_threads() {
+        let invocation = parse(args("--threads 8 --no-wait ingest a.txt")).unwrap();
+        assert_eq!(invocation.threads, Some(8));
+        assert_eq!(invocation.lock, LockMode::Fail);
+    }
+
+    #[test]
+    fn it_parses_tokenizer() {
+        let invocation = parse(args("--tokenizer lines ingest a.txt")).unwrap();
+        assert_eq!(invocation.tokenizer, Some("lines".to_string()));
+        assert!(parse(args("--tokenizer nltk ingest a.txt")).is_err());
+        assert_eq!(
+            parse(args("--tokenizer unicode:nfkc ingest a.txt"))
+                .unwrap()
+                .tokenizer,
+            Some("unicode:nfkc".to_string())
+        );
+        assert!(parse(args("ingest a.txt --tokenizer")).is_err());
+
+        let invocation = parse(args(
+            "--tokenizer prose --abbreviations abbr.txt ingest a.txt",
+        ))
+        .unwrap();
+        assert_eq!(invocation.abbreviations, Some(PathBuf::from("abbr.txt")));
+        assert!(parse(args("--abbreviations abbr.txt ingest a.txt")).is_err());
+        assert!(parse(args(
+            "--tokenizer unicode --abbreviations abbr.txt ingest a.txt"
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn it_parses_min_support() {
+        let invocation = parse(args("--min-support 3 ingest a.txt")).unwrap();
+        assert_eq!(invocation.support, Some(Support::Count(3)));
+        assert_eq!(
+            parse(args("--min-support 10% ingest a.txt"))
+                .unwrap()
+                .support,
+            Some(Support::Fraction(0.1))
+        );
+        assert_eq!(parse(args("ingest a.txt")).unwrap().support, None);
+        assert!(parse(args("--min-support 0 ingest a.txt")).is_err());
+        assert!(parse(args("--min-support lots ingest a.txt")).is_err());
+        assert_eq!(
+            parse(args("--min-support 2 rescan")).unwrap().command,
+            Command::Rescan
+        );
+    }
+
+    #[test]
+    fn it_parses_convert() {
+        let invocation = parse(args("convert --repo r.yaml --state s.bin")).unwrap();
+        assert_eq!(invocation.command, Command::Convert { force: false });
+        assert_eq!(invocation.workspace.repo, PathBuf::from("r.yaml"));
+        assert_eq!(invocation.workspace.state, PathBuf::from("s.bin"));
+        assert_eq!(
+            parse(args("convert --force")).unwrap().command,
+            Command::Convert { force: true }
+        );
+        assert!(parse(args("compact --force")).is_err());
+        assert_eq!(parse(args("compact")).unwrap().command, Command::Compact);
+    }
+
+    #[test]
+    fn it_parses_contents_queries() {
+        let invocation = parse(args("query --contains a --contains b --at d@1,1")).unwrap();
+        assert_eq!(
+            invocation.command,
+            Command::Query(Query {
+                contains: vec!["a".to_string(), "b".to_string()],
+                at: Some(("d".to_string(), vec![1, 1])),
+                ..Query::default()
+            })
+        );
+        assert!(parse(args("query --at d")).is_err());
+        assert!(parse(args("query --at d@x")).is_err());
+    }
+
+    #[test]
+    fn it_parses_export() {
+        assert_eq!(
+            parse(args("export")).unwrap().command,
+            Command::Export(Format::Yaml)
+        );
+        assert_eq!(
+            parse(args("export --format jsonl")).unwrap().command,
+            Command::Export(Format::Jsonl)
+        );
+        assert_eq!(
+            parse(args("--format csv export")).unwrap().command,
+            Command::Export(Format::Csv)
+        );
+        assert!(parse(args("export --format xml")).is_err());
+        assert!(parse(args("stats --format csv")).is_err());
+    }
+
+    #[test]
+    fn it_parses_dot() {
+        assert_eq!(parse(args("dot")).unwrap().command, Command::Dot(None));
+        assert_eq!(
+            parse(args("dot --around cat")).unwrap().command,
+            Command::Dot(Some(("cat".to_string(), 1)))
+        );
+        assert_eq!(
+            parse(args("dot --around cat --hops 3")).unwrap().command,
+            Command::Dot(Some(("cat".to_string(), 3)))
+        );
+        assert!(parse(args("dot --hops 3")).is_err());
+        assert!(parse(args("dot --around cat --hops x")).is_err());
+        assert!(parse(args("stats --around cat")).is_err());
+    }
+
+    #[test]
+    fn it_parses_show() {
+        assert_eq!(
+            parse(args("show cat")).unwrap().command,
+            Command::Show("cat".to_string())
+        );
+        assert!(parse(args("show")).is_err());
+        assert!(parse(args("show cat dog")).is_err());
+    }
+
+    #[test]
+    fn it_parses_help() {
+        assert_eq!(parse(args("stats --help")).unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn it_rejects_bad_invocations() {
+        assert!(parse(args("")).is_err());
+        assert!(parse(args("ingest")).is_err());
+        assert!(parse(args("frobnicate")).is_err());
+        assert!(parse(args("stats extra")).is_err());
+        assert!(parse(args("stats --origin a")).is_err());
+        assert!(parse(args("query --size 2xq")).is_err());
+        assert!(parse(args("query --dir")).is_err());
+        assert!(parse(args("--threads 0 ingest a.txt")).is_err());
+    }
+
+    #[test]
+    fn it_rejects_options_for_other_commands() {
+        assert!(parse(args("--threads 4 rescan")).is_ok());
+        assert!(parse(args("--threads 4 query")).is_err());
+        assert!(parse(args("--min-support 2 stats")).is_err());
+        assert!(parse(args("--min-support 2 export")).is_err());
+        assert!(parse(args("--tokenizer lines show cat")).is_err());
+        assert!(parse(args("--tokenizer lines rescan")).is_err());
+        assert!(parse(args("--tokenizer prose --abbreviations abbr.txt dot")).is_err());
+        assert!(parse(args("--store sled ingest a.txt")).is_ok());
+        assert!(parse(args("--store sled stats")).is_err());
+        assert!(parse(args("--store tape ingest a.txt")).is_err());
+    }
+}
@@ -1,35 +1,23 @@
+use crate::error::{Error, Result};
+use crate::ortho::resolve;
+use crate::tokenizer::{Classic, Tokenizer};
 use nohash_hasher::IntMap;
 use nohash_hasher::IntSet;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
+use std::str::FromStr;
 use string_interner::StringInterner;
 use string_interner::Symbol;
 
-pub fn clean_sentences(sentences: String) -> Vec<Vec<String>> {
-    sentences
-        .split(|x| x == '.' || x == '!' || x == '?')
-        .filter(|x| !x.is_empty())
-        .map(|sentence| {
-            sentence
-                .replace(";", "")
-                .replace("\'", "")
-                .replace(":", "")
-                .replace(",", "")
-                .to_lowercase()
-                .split_ascii_whitespace()
-                .map(|x| x.to_string())
-                .collect()
-        })
-        .collect()
-}
-
 pub struct Config {
     vocabulary: IntSet<usize>,
     forward: IntMap<usize, IntSet<usize>>,
     backward: IntMap<usize, IntSet<usize>>,
+    counts: IntMap<usize, IntMap<usize, usize>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -37,17 +25,22 @@ pub struct LiteralConfig {
     vocabulary: HashSet<String>,
     forward: HashMap<String, HashSet<String>>,
     backward: HashMap<String, HashSet<String>>,
+    // occurrences of each forward edge; files written before counts were
+    // kept have none, and their edges count once
+    #[serde(default)]
+    counts: HashMap<String, HashMap<String, usize>>,
 }
 
 impl LiteralConfig {
     pub fn from_raw(raw: String) -> LiteralConfig {
-        Self::new(clean_sentences(raw))
+        Self::new(Classic.tokenize(&raw))
     }
 
     pub fn new(sentences: Vec<Vec<String>>) -> LiteralConfig {
         let mut vocabulary = HashSet::default();
         let mut forward = HashMap::default();
         let mut backward = HashMap::default();
+        let mut counts: HashMap<String, HashMap<String, usize>> = HashMap::default();
 
         for sentence in sentences {
             if sentence.len() < 2 {
@@ -63,6 +56,11 @@ impl LiteralConfig {
                     .entry(word.clone())
                     .or_insert_with(HashSet::default)
                     .insert(next_word.clone());
+                *counts
+                    .entry(word.clone())
+                    .or_default()
+                    .entry(next_word.clone())
+                    .or_insert(0) += 1;
 
                 backward
                     .entry(next_word)
@@ -74,42 +72,77 @@ impl LiteralConfig {
             vocabulary,
             forward,
             backward,
+            counts,
         }
     }
 
-    pub fn save(&self, mut target: File) {
-        std::io::Write::write_fmt(
-            &mut target,
-            format_args!("{}", serde_yaml::to_string(&self).unwrap()),
-        )
-        .unwrap();
+    pub fn count(&self, from: &str, to: &str) -> usize {
+        match self.forward.get(from) {
+            Some(next) if next.contains(to) => self
+                .counts
+                .get(from)
+                .and_then(|counts| counts.get(to))
+                .cloned()
+                .unwrap_or(1),
+            _ => 0,
+        }
+    }
+
+    pub fn save(&self, mut target: impl Write) -> Result<()> {
+        target.write_all(serde_yaml::to_string(&self)?.as_bytes())?;
+        Ok(())
     }
 
-    pub fn merge(&mut self, other: LiteralConfig) {
+    pub fn merge(&mut self, other: LiteralConfig) -> BTreeSet<(String, String)> {
+        let mut added = BTreeSet::default();
+        for (k, v) in &other.forward {
+            for next in v {
+                let count = self.count(k, next) + other.count(k, next);
+                self.counts
+                    .entry(k.clone())
+                    .or_default()
+                    .insert(next.clone(), count);
+            }
+        }
+
         for x in other.vocabulary {
             self.vocabulary.insert(x);
         }
 
         for (k, v) in other.forward {
-            self.forward
-                .entry(k)
-                .or_insert_with(HashSet::default)
-                .extend(v);
+            let existing = self.forward.entry(k.clone()).or_default();
+            for next in v {
+                if existing.insert(next.clone()) {
+                    added.insert((k.clone(), next));
+                }
+            }
         }
 
         for (k, v) in other.backward {
-            self.backward
-                .entry(k)
-                .or_insert_with(HashSet::default)
-                .extend(v);
+            self.backward.entry(k).or_default().extend(v);
         }
+        added
     }
 
     pub fn intern(&self, string_interner: &mut StringInterner) -> Config {
+        let forward = Self::intern_hashmap(&self.forward, string_interner);
+        let mut counts: IntMap<usize, IntMap<usize, usize>> = IntMap::default();
+        for (k, v) in &self.forward {
+            for next in v {
+                counts
+                    .entry(string_interner.get_or_intern(k).to_usize())
+                    .or_default()
+                    .insert(
+                        string_interner.get_or_intern(next).to_usize(),
+                        self.count(k, next),
+                    );
+            }
+        }
         Config {
             vocabulary: Self::intern_hashset(&self.vocabulary, string_interner),
-            forward: Self::intern_hashmap(&self.forward, string_interner),
+            forward,
             backward: Self::intern_hashmap(&self.backward, string_interner),
+            counts,
         }
     }
 
@@ -135,10 +168,10 @@ impl LiteralConfig {
         new_hm
     }
 
-    pub fn load(mut source: File) -> LiteralConfig {
+    pub fn load(mut source: impl Read) -> Result<LiteralConfig> {
         let mut contents = String::new();
-        source.read_to_string(&mut contents).unwrap();
-        serde_yaml::from_str(&contents).unwrap()
+        source.read_to_string(&mut contents)?;
+        Ok(serde_yaml::from_str(&contents)?)
     }
 }
 
@@ -151,22 +184,236 @@ impl Config {
         self.backward.get(&word)
     }
 
+    // each neighbour with the number of times the edge was seen
+    pub fn project_forward_weighted(
+        &self,
+        word: usize,
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.counts
+            .get(&word)
+            .into_iter()
+            .flatten()
+            .map(|(next, count)| (*next, *count))
+    }
+
+    pub fn project_backward_weighted(
+        &self,
+        word: usize,
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.project_backward(word)
+            .into_iter()
+            .flatten()
+            .map(move |previous| (*previous, self.count(*previous, word)))
+    }
+
+    pub fn count(&self, from: usize, to: usize) -> usize {
+        self.counts
+            .get(&from)
+            .and_then(|counts| counts.get(&to))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    pub fn out_count(&self, from: usize) -> usize {
+        self.counts
+            .get(&from)
+            .map_or(0, |counts| counts.values().sum())
+    }
+
+    // the neighbours along edges that meet the support, looked up as the search
+    // asks so the graph is never copied; borrowed when every edge qualifies
+    pub fn project_forward_supported(
+        &self,
+        word: usize,
+        support: Support,
+    ) -> Option<Cow<'_, IntSet<usize>>> {
+        let next = self.forward.get(&word)?;
+        if support.is_trivial() {
+            return Some(Cow::Borrowed(next));
+        }
+        let out = self.out_count(word);
+        Self::admitted(
+            next.iter()
+                .filter(|to| support.meets(self.count(word, **to), || out)),
+        )
+    }
+
+    pub fn project_backward_supported(
+        &self,
+        word: usize,
+        support: Support,
+    ) -> Option<Cow<'_, IntSet<usize>>> {
+        let previous = self.backward.get(&word)?;
+        if support.is_trivial() {
+            return Some(Cow::Borrowed(previous));
+        }
+        Self::admitted(
+            previous
+                .iter()
+                .filter(|from| support.admits(self, **from, word)),
+        )
+    }
+
+    fn admitted<'a>(words: impl Iterator<Item = &'a usize>) -> Option<Cow<'a, IntSet<usize>>> {
+        let admitted: IntSet<usize> = words.cloned().collect();
+        if admitted.is_empty() {
+            None
+        } else {
+            Some(Cow::Owned(admitted))
+        }
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &usize> {
         self.vocabulary.iter()
     }
 
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.forward
+            .iter()
+            .flat_map(|(from, to)| to.iter().map(move |to| (*from, *to)))
+    }
+
+    // one more occurrence of from -> to; true if the edge is new
+    pub fn add(&mut self, from: usize, to: usize) -> bool {
+        let count = self.count(from, to) + 1;
+        self.set_count(from, to, count)
+    }
+
+    pub fn set_count(&mut self, from: usize, to: usize, count: usize) -> bool {
+        self.vocabulary.insert(from);
+        self.vocabulary.insert(to);
+        self.counts.entry(from).or_default().insert(to, count);
+        self.backward.entry(to).or_default().insert(from);
+        self.forward.entry(from).or_default().insert(to)
+    }
+
     pub fn from_sentences(raw: String) -> (Config, StringInterner) {
         let mut interner = StringInterner::default();
-        let config = LiteralConfig::new(clean_sentences(raw)).intern(&mut interner);
+        let config = LiteralConfig::new(Classic.tokenize(&raw)).intern(&mut interner);
         (config, interner)
     }
+
+    pub fn new() -> Config {
+        Config {
+            vocabulary: IntSet::default(),
+            forward: IntMap::default(),
+            backward: IntMap::default(),
+            counts: IntMap::default(),
+        }
+    }
+
+    pub fn merge(&mut self, other: Config) -> BTreeSet<(usize, usize)> {
+        let mut added = BTreeSet::default();
+        self.vocabulary.extend(other.vocabulary);
+
+        for (k, v) in other.counts {
+            for (next, count) in v {
+                if self.set_count(k, next, self.count(k, next) + count) {
+                    added.insert((k, next));
+                }
+            }
+        }
+        added
+    }
+
+    pub fn unintern(&self, interner: &StringInterner) -> Result<LiteralConfig> {
+        Ok(LiteralConfig {
+            vocabulary: Self::unintern_hashset(&self.vocabulary, interner)?,
+            forward: Self::unintern_hashmap(&self.forward, interner)?,
+            backward: Self::unintern_hashmap(&self.backward, interner)?,
+            counts: self
+                .counts
+                .iter()
+                .map(|(k, v)| {
+                    Ok((
+                        resolve(interner, *k)?,
+                        v.iter()
+                            .map(|(next, count)| Ok((resolve(interner, *next)?, *count)))
+                            .collect::<Result<_>>()?,
+                    ))
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    fn unintern_hashset(hs: &IntSet<usize>, interner: &StringInterner) -> Result<HashSet<String>> {
+        hs.iter().map(|word| resolve(interner, *word)).collect()
+    }
+
+    fn unintern_hashmap(
+        hm: &IntMap<usize, IntSet<usize>>,
+        interner: &StringInterner,
+    ) -> Result<HashMap<String, HashSet<String>>> {
+        hm.iter()
+            .map(|(k, v)| Ok((resolve(interner, *k)?, Self::unintern_hashset(v, interner)?)))
+            .collect()
+    }
+}
+
+// the least an edge must be seen to take part in search: an absolute count,
+// or a share of all occurrences leaving its source word
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Support {
+    Count(usize),
+    Fraction(f64),
+}
+
+impl Default for Support {
+    fn default() -> Self {
+        Support::Count(1)
+    }
+}
+
+impl FromStr for Support {
+    type Err = Error;
+
+    fn from_str(spec: &str) -> Result<Support> {
+        let invalid = || Error::Format(format!("invalid support {}", spec));
+        let support = if let Some(percent) = spec.strip_suffix('%') {
+            Support::Fraction(percent.parse::<f64>().map_err(|_| invalid())? / 100.0)
+        } else if spec.contains('.') {
+            Support::Fraction(spec.parse().map_err(|_| invalid())?)
+        } else {
+            Support::Count(spec.parse().map_err(|_| invalid())?)
+        };
+        match support {
+            Support::Count(count) if count >= 1 => Ok(support),
+            Support::Fraction(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(support),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Support {
+    pub fn admits(&self, config: &Config, from: usize, to: usize) -> bool {
+        self.meets(config.count(from, to), || config.out_count(from))
+    }
+
+    // out is only worked out for fractions
+    fn meets(&self, count: usize, out: impl FnOnce() -> usize) -> bool {
+        match self {
+            Support::Count(least) => count >= *least && count > 0,
+            Support::Fraction(least) => count > 0 && count as f64 >= least * out() as f64,
+        }
+    }
+
+    pub fn is_trivial(&self) -> bool {
+        *self == Support::default()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
-
     use super::*;
+    use crate::Workspace;
+    use std::collections::BTreeMap;
+    use std::fs::File;
 
     fn string_to_usize(interner: &StringInterner, string: &str) -> usize {
         interner.get(string).unwrap().to_usize()
@@ -229,9 +476,12 @@ mod tests {
         let config = literal_config.intern(&mut interner);
         let config2 = literal_config2.intern(&mut interner);
 
-        literal_config.merge(literal_config2);
+        let added = literal_config.merge(literal_config2);
         let res = literal_config.intern(&mut interner);
 
+        assert_eq!(added.len(), 4);
+        assert!(added.contains(&("e".to_string(), "f".to_string())));
+
         assert_eq!(res.vocabulary.len(), 8);
         assert_eq!(config.vocabulary.len(), 4);
         assert_eq!(config2.vocabulary.len(), 4);
@@ -243,18 +493,197 @@ mod tests {
         assert_eq!(config2.backward.len(), 3);
     }
 
+    #[test]
+    fn it_merges_interned() {
+        let mut interner = StringInterner::default();
+        let mut config =
+            LiteralConfig::from_raw("a b. c d. a c. b d.".to_string()).intern(&mut interner);
+        let config2 = LiteralConfig::from_raw("e f. a b. b e.".to_string()).intern(&mut interner);
+
+        let added = config.merge(config2);
+
+        assert_eq!(
+            added,
+            vec![
+                (
+                    string_to_usize(&interner, "e"),
+                    string_to_usize(&interner, "f")
+                ),
+                (
+                    string_to_usize(&interner, "b"),
+                    string_to_usize(&interner, "e")
+                ),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(config.vocabulary.len(), 6);
+        assert_eq!(
+            config
+                .project_forward(string_to_usize(&interner, "b"))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let mut literal_config = LiteralConfig::from_raw("a b. c d. a c. b d.".to_string());
+        literal_config.merge(LiteralConfig::from_raw("e f. a b. b e.".to_string()));
+        assert_eq!(config.unintern(&interner).unwrap(), literal_config);
+    }
+
+    #[test]
+    fn it_rebuilds_from_edges() {
+        let (config, interner) = Config::from_sentences("a b c. c d. a c.".to_string());
+        let mut rebuilt = Config::new();
+        for (from, to) in config.edges() {
+            assert!(rebuilt.add(from, to));
+        }
+        assert!(!rebuilt.add(
+            string_to_usize(&interner, "a"),
+            string_to_usize(&interner, "b")
+        ));
+
+        assert_eq!(rebuilt.vocabulary, config.vocabulary);
+        assert_eq!(rebuilt.forward, config.forward);
+        assert_eq!(rebuilt.backward, config.backward);
+    }
+
+    #[test]
+    fn it_counts_edges() {
+        let mut literal_config = LiteralConfig::from_raw("a b. a b c. b c. a c.".to_string());
+        assert_eq!(literal_config.count("a", "b"), 2);
+        assert_eq!(literal_config.count("b", "a"), 0);
+        literal_config.merge(LiteralConfig::from_raw("a b. c d.".to_string()));
+        assert_eq!(literal_config.count("a", "b"), 3);
+        assert_eq!(literal_config.count("c", "d"), 1);
+
+        let mut interner = StringInterner::default();
+        let mut config = literal_config.intern(&mut interner);
+        let id = |word| string_to_usize(&interner, word);
+        assert_eq!(config.count(id("a"), id("b")), 3);
+        assert_eq!(config.count(id("b"), id("c")), 2);
+        assert_eq!(config.count(id("d"), id("a")), 0);
+
+        // ids depend on interning order, so compare by word
+        let by_word = |weighted: Vec<(usize, usize)>| -> BTreeMap<String, usize> {
+            weighted
+                .into_iter()
+                .map(|(word, count)| (resolve(&interner, word).unwrap(), count))
+                .collect()
+        };
+        let forward = by_word(config.project_forward_weighted(id("a")).collect());
+        assert_eq!(
+            forward,
+            vec![("b".to_string(), 3), ("c".to_string(), 1)]
+                .into_iter()
+                .collect()
+        );
+        let backward = by_word(config.project_backward_weighted(id("c")).collect());
+        assert_eq!(
+            backward,
+            vec![("a".to_string(), 1), ("b".to_string(), 2)]
+                .into_iter()
+                .collect()
+        );
+
+        let added =
+            config.merge(LiteralConfig::from_raw("a b. d a.".to_string()).intern(&mut interner));
+        let id = |word| string_to_usize(&interner, word);
+        assert_eq!(added.len(), 1);
+        assert_eq!(config.count(id("a"), id("b")), 4);
+        assert!(!config.add(id("a"), id("b")));
+        assert_eq!(config.count(id("a"), id("b")), 5);
+
+        literal_config.merge(LiteralConfig::from_raw("a b. d a. a b.".to_string()));
+        assert_eq!(config.unintern(&interner).unwrap(), literal_config);
+    }
+
+    #[test]
+    fn it_counts_edges_from_files_without_counts_once() {
+        let yaml = "vocabulary: [a, b]\nforward: {a: [b]}\nbackward: {b: [a]}\n";
+        let mut literal_config = LiteralConfig::load(yaml.as_bytes()).unwrap();
+        assert_eq!(literal_config.count("a", "b"), 1);
+
+        literal_config.merge(LiteralConfig::from_raw("a b.".to_string()));
+        assert_eq!(literal_config.count("a", "b"), 2);
+
+        let mut interner = StringInterner::default();
+        let mut config = LiteralConfig::load(yaml.as_bytes())
+            .unwrap()
+            .intern(&mut interner);
+        let (a, b) = (
+            string_to_usize(&interner, "a"),
+            string_to_usize(&interner, "b"),
+        );
+        assert_eq!(config.count(a, b), 1);
+        config.merge(LiteralConfig::from_raw("a b.".to_string()).intern(&mut interner));
+        assert_eq!(config.count(a, b), 2);
+    }
+
+    #[test]
+    fn it_filters_edges_below_support() {
+        let (config, interner) =
+            Config::from_sentences("a b. a b. a b. a c. b c. b c. d e.".to_string());
+        let id = |word| string_to_usize(&interner, word);
+
+        let count = Support::Count(2);
+        let forward = config.project_forward_supported(id("a"), count).unwrap();
+        assert!(forward.contains(&id("b")) && !forward.contains(&id("c")));
+        let backward = config.project_backward_supported(id("c"), count).unwrap();
+        assert!(backward.contains(&id("b")) && !backward.contains(&id("a")));
+        assert!(config.project_forward_supported(id("d"), count).is_none());
+        assert!(config.project_backward_supported(id("e"), count).is_none());
+
+        let fraction = Support::Fraction(0.5);
+        let forward = config.project_forward_supported(id("a"), fraction).unwrap();
+        assert!(forward.contains(&id("b")) && !forward.contains(&id("c")));
+        assert!(config
+            .project_forward_supported(id("d"), fraction)
+            .unwrap()
+            .contains(&id("e")));
+        assert!(config
+            .project_backward_supported(id("c"), fraction)
+            .unwrap()
+            .contains(&id("b")));
+
+        assert_eq!(config.count(id("a"), id("c")), 1);
+        assert!(matches!(
+            config.project_forward_supported(id("a"), Support::default()),
+            Some(Cow::Borrowed(_))
+        ));
+    }
+
+    #[test]
+    fn it_parses_support() {
+        assert_eq!("3".parse::<Support>().unwrap(), Support::Count(3));
+        assert_eq!("0.25".parse::<Support>().unwrap(), Support::Fraction(0.25));
+        assert_eq!("5%".parse::<Support>().unwrap(), Support::Fraction(0.05));
+        for bad in ["0", "1.5", "0.0", "x", "-2", "%"] {
+            assert!(bad.parse::<Support>().is_err(), "{}", bad);
+        }
+        assert!(Support::Count(1).is_trivial());
+        assert!(!Support::Fraction(1.0).is_trivial());
+    }
+
     #[test]
     fn it_saves_and_loads() {
-        let filename = "temp.yaml";
+        let workspace = Workspace::scratch("config-saves");
+        workspace.prepare().unwrap();
         let literal_config = LiteralConfig::from_raw("a b. c d. a c. b d.".to_string());
 
-        let f = File::create(filename).unwrap();
-        literal_config.save(f);
+        let f = File::create(&workspace.config).unwrap();
+        literal_config.save(f).unwrap();
 
-        let f2 = File::open(filename).unwrap();
+        let f2 = File::open(&workspace.config).unwrap();
 
-        let res = LiteralConfig::load(f2);
-        fs::remove_file(filename).unwrap();
+        let res = LiteralConfig::load(f2).unwrap();
+        workspace.clean();
         assert_eq!(literal_config, res);
     }
+
+    #[test]
+    fn it_reports_corrupt_files() {
+        let res = LiteralConfig::load("vocabulary: [unclosed".as_bytes());
+        assert!(matches!(res, Err(Error::Yaml(_))));
+    }
 }
@@ -0,0 +1,325 @@
+use crate::binary::{decode, encode, write_varint, Reader};
+use crate::error::Result;
+use crate::ortho::{MultiSet, Ortho};
+use crate::repo::{Repo, RepoStats, RepoStore};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+// keys are (size, word) prefixes followed by the ortho itself, so each index
+// bucket is a prefix scan and values stay empty. contents keys use an empty
+// size so every tree shares one layout.
+// orthos added since the last flush stay in memory: the engine flushes only
+// once the journal holds them, so sled is never ahead of the journal
+pub struct DiskRepo {
+    db: sled::Db,
+    origin: sled::Tree,
+    hops: sled::Tree,
+    contents: sled::Tree,
+    pending: Repo,
+}
+
+impl DiskRepo {
+    // without a flusher thread nothing outlives a drop, so the same process
+    // can open the store again
+    pub fn open(path: impl AsRef<Path>) -> Result<DiskRepo> {
+        let db = sled::Config::new().path(path).flush_every_ms(None).open()?;
+        Ok(DiskRepo {
+            origin: db.open_tree("origin")?,
+            hops: db.open_tree("hops")?,
+            contents: db.open_tree("contents")?,
+            db,
+            pending: Repo::new(),
+        })
+    }
+
+    // origin goes last: contains() reads it, so an ortho cut off mid-flush
+    // is written whole again when the journal replays
+    pub fn flush(&mut self) -> Result<()> {
+        let mut origin = sled::Batch::default();
+        let mut hops = sled::Batch::default();
+        let mut contents = sled::Batch::default();
+        for ortho in self.pending.iter() {
+            let size = ortho.size();
+            origin.insert(Self::key(&size, ortho.origin(), ortho), &[]);
+            for hop in ortho.hop() {
+                hops.insert(Self::key(&size, *hop, ortho), &[]);
+            }
+            for (_location, word) in ortho.cells() {
+                contents.insert(Self::key(&MultiSet::new(), *word, ortho), &[]);
+            }
+        }
+        self.hops.apply_batch(hops)?;
+        self.contents.apply_batch(contents)?;
+        self.origin.apply_batch(origin)?;
+        self.db.flush()?;
+        self.pending = Repo::new();
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<RepoStats> {
+        let mut shapes = BTreeMap::default();
+        let mut origins = BTreeSet::default();
+        for ortho in self.orthos() {
+            let ortho = ortho?;
+            *shapes.entry(ortho.shape()).or_insert(0) += 1;
+            origins.insert(ortho.origin());
+        }
+
+        let mut buckets: HashMap<Vec<u8>, usize> = HashMap::default();
+        for key in self.hops.iter().keys() {
+            let key = key?;
+            let mut reader = Reader::new(&key);
+            Self::skip_prefix(&mut reader)?;
+            *buckets
+                .entry(key[..reader.position()].to_vec())
+                .or_insert(0) += 1;
+        }
+        for ortho in self.pending.iter() {
+            for hop in ortho.hop() {
+                *buckets
+                    .entry(Self::prefix(&ortho.size(), *hop))
+                    .or_insert(0) += 1;
+            }
+        }
+        let mut hop_fan_out = BTreeMap::default();
+        for fan_out in buckets.into_values() {
+            *hop_fan_out.entry(fan_out).or_insert(0) += 1;
+        }
+
+        Ok(RepoStats {
+            orthos: self.len(),
+            shapes,
+            origins: origins.len(),
+            hop_fan_out,
+            index_bytes: self.db.size_on_disk()? as usize + self.pending.stats().index_bytes,
+        })
+    }
+
+    fn prefix(size: &MultiSet, word: usize) -> Vec<u8> {
+        let mut buffer = vec![];
+        write_varint(&mut buffer, size.items().count());
+        for length in size.items() {
+            write_varint(&mut buffer, *length);
+            write_varint(&mut buffer, size.count(*length));
+        }
+        write_varint(&mut buffer, word);
+        buffer
+    }
+
+    fn key(size: &MultiSet, word: usize, ortho: &Ortho) -> Vec<u8> {
+        let mut key = Self::prefix(size, word);
+        encode(&mut key, ortho);
+        key
+    }
+
+    fn ortho(key: &[u8]) -> Result<Ortho> {
+        let mut reader = Reader::new(key);
+        Self::skip_prefix(&mut reader)?;
+        decode(&mut reader, Ok)
+    }
+
+    fn skip_prefix(reader: &mut Reader) -> Result<()> {
+        for _ in 0..reader.varint()? {
+            reader.varint()?;
+            reader.varint()?;
+        }
+        reader.varint()?;
+        Ok(())
+    }
+
+    fn bucket<'a>(
+        tree: &sled::Tree,
+        prefix: Vec<u8>,
+        pending: Option<Cow<'a, BTreeSet<Ortho>>>,
+    ) -> Result<Option<Cow<'a, BTreeSet<Ortho>>>> {
+        let mut bucket = BTreeSet::default();
+        for key in tree.scan_prefix(prefix).keys() {
+            bucket.insert(Self::ortho(&key?)?);
+        }
+        Ok(match pending {
+            Some(pending) if bucket.is_empty() => Some(pending),
+            Some(pending) => {
+                bucket.extend(pending.iter().cloned());
+                Some(Cow::Owned(bucket))
+            }
+            None if bucket.is_empty() => None,
+            None => Some(Cow::Owned(bucket)),
+        })
+    }
+}
+
+impl RepoStore for DiskRepo {
+    fn add(&mut self, ortho: Ortho) -> Result<()> {
+        if !self.contains(&ortho)? {
+            self.pending.add(ortho)?;
+        }
+        Ok(())
+    }
+
+    fn contains(&self, ortho: &Ortho) -> Result<bool> {
+        Ok(self.pending.contains(ortho)?
+            || self
+                .origin
+                .contains_key(Self::key(&ortho.size(), ortho.origin(), ortho))?)
+    }
+
+    fn find_by_size_and_origin(
+        &self,
+        size: MultiSet,
+        origin: usize,
+    ) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>> {
+        let pending = self.pending.find_by_size_and_origin(size.clone(), origin)?;
+        Self::bucket(&self.origin, Self::prefix(&size, origin), pending)
+    }
+
+    fn find_by_size_and_hop(
+        &self,
+        size: MultiSet,
+        hop: usize,
+    ) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>> {
+        let pending = self.pending.find_by_size_and_hop(size.clone(), hop)?;
+        Self::bucket(&self.hops, Self::prefix(&size, hop), pending)
+    }
+
+    fn find_by_word(&self, word: usize) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>> {
+        let pending = self.pending.find_by_word(word)?;
+        Self::bucket(
+            &self.contents,
+            Self::prefix(&MultiSet::new(), word),
+            pending,
+        )
+    }
+
+    fn orthos(&self) -> Box<dyn Iterator<Item = Result<Cow<'_, Ortho>>> + '_> {
+        Box::new(
+            self.origin
+                .iter()
+                .keys()
+                .map(|key| Ok(Cow::Owned(Self::ortho(&key?)?)))
+                .chain(self.pending.orthos()),
+        )
+    }
+
+    fn len(&self) -> usize {
+        self.origin.len() + self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Support;
+    use crate::search::make_atoms;
+    use crate::workspace::Workspace;
+    use crate::Config;
+
+    fn scratch(name: &str) -> (Workspace, std::path::PathBuf) {
+        let workspace = Workspace::scratch(name);
+        let path = workspace.store_path();
+        (workspace, path)
+    }
+
+    #[test]
+    fn it_indexes_like_the_in_memory_repo() {
+        let (workspace, path) = scratch("disk-indexes");
+        let mut disk = DiskRepo::open(&path).unwrap();
+        let mut memory = Repo::new();
+        let ortho = Ortho::new(1, 2, 3, 4);
+        let pending = Ortho::new(1, 5, 3, 6);
+        disk.add(ortho.clone()).unwrap();
+        disk.flush().unwrap();
+        disk.add(ortho.clone()).unwrap();
+        disk.add(pending.clone()).unwrap();
+        memory.add(ortho.clone()).unwrap();
+        memory.add(pending.clone()).unwrap();
+
+        assert_eq!(disk.len(), 2);
+        assert!(disk.contains(&ortho).unwrap());
+        assert!(disk.contains(&pending).unwrap());
+        assert!(!disk.contains(&Ortho::new(1, 3, 2, 5)).unwrap());
+        assert_eq!(
+            disk.find_by_size_and_origin(ortho.size(), 1).unwrap(),
+            memory.find_by_size_and_origin(ortho.size(), 1).unwrap()
+        );
+        for hop in [2, 3, 5] {
+            assert_eq!(
+                disk.find_by_size_and_hop(ortho.size(), hop).unwrap(),
+                memory.find_by_size_and_hop(ortho.size(), hop).unwrap()
+            );
+        }
+        for word in [1, 4, 6] {
+            assert_eq!(
+                disk.find_by_word(word).unwrap(),
+                memory.find_by_word(word).unwrap()
+            );
+        }
+        assert_eq!(disk.find_by_size_and_origin(ortho.size(), 2).unwrap(), None);
+        assert_eq!(
+            disk.find_by_size_and_hop(MultiSet::from_shape(&[2, 3]), 2)
+                .unwrap(),
+            None
+        );
+        assert_eq!(disk.find_by_word(7).unwrap(), None);
+        assert_eq!(disk.stats().unwrap().orthos, 2);
+        assert_eq!(
+            disk.stats().unwrap().hop_fan_out,
+            memory.stats().hop_fan_out
+        );
+        drop(disk);
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_reopens_what_it_flushed() {
+        let (workspace, path) = scratch("disk-reopens");
+        let flushed = Ortho::new(1, 2, 3, 4);
+        let unflushed = Ortho::new(5, 6, 7, 8);
+        let mut disk = DiskRepo::open(&path).unwrap();
+        disk.add(flushed.clone()).unwrap();
+        disk.flush().unwrap();
+        disk.add(unflushed.clone()).unwrap();
+        drop(disk);
+
+        let reopened = DiskRepo::open(&path).unwrap();
+        assert_eq!(reopened.len(), 1);
+        assert!(reopened.contains(&flushed).unwrap());
+        assert!(!reopened.contains(&unflushed).unwrap());
+        assert_eq!(
+            reopened.find_by_word(4).unwrap().unwrap().into_owned(),
+            std::iter::once(flushed).collect()
+        );
+        drop(reopened);
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_folds_the_same_orthos_as_memory() {
+        let (workspace, path) = scratch("disk-folds");
+        let (config, _interner) = Config::from_sentences(
+            "a b e g. c d f h. a c. b d. e f. g h. i j. k l. i k. j l. a i. b j. c k. d l."
+                .to_string(),
+        );
+
+        let mut memory = Repo::new();
+        make_atoms(&config, Support::default(), &mut memory, 2).unwrap();
+        let mut disk = DiskRepo::open(&path).unwrap();
+        make_atoms(&config, Support::default(), &mut disk, 2).unwrap();
+        disk.flush().unwrap();
+
+        let folded: BTreeSet<Ortho> = disk
+            .orthos()
+            .map(|o| o.map(Cow::into_owned))
+            .collect::<Result<_>>()
+            .unwrap();
+        let expected: BTreeSet<Ortho> = memory.iter().cloned().collect();
+        assert!(expected.len() > 2);
+        assert_eq!(folded, expected);
+        assert_eq!(disk.len(), memory.len());
+        assert!(make_atoms(&config, Support::default(), &mut disk, 1)
+            .unwrap()
+            .is_empty());
+        drop(disk);
+        workspace.clean();
+    }
+}
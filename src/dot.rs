@@ -0,0 +1,185 @@
+use crate::error::Result;
+use crate::ortho::{resolve, Ortho};
+use crate::repo::RepoStore;
+use crate::Config;
+use std::collections::BTreeSet;
+use std::io::Write;
+use string_interner::StringInterner;
+
+// every word within `hops` steps of `word`, following edges either way
+pub fn neighbourhood(config: &Config, word: usize, hops: usize) -> BTreeSet<usize> {
+    let mut seen: BTreeSet<usize> = std::iter::once(word).collect();
+    let mut frontier = vec![word];
+    for _ in 0..hops {
+        let mut next = vec![];
+        for word in frontier {
+            let neighbours = config
+                .project_forward(word)
+                .into_iter()
+                .chain(config.project_backward(word))
+                .flatten();
+            for neighbour in neighbours {
+                if seen.insert(*neighbour) {
+                    next.push(*neighbour);
+                }
+            }
+        }
+        frontier = next;
+    }
+    seen
+}
+
+// one step along any axis is a forward edge in the graph
+pub fn ortho_edges(ortho: &Ortho) -> BTreeSet<(usize, usize)> {
+    let axes = ortho.axes();
+    ortho
+        .cells()
+        .flat_map(|(location, from)| {
+            axes.iter()
+                .filter_map(move |axis| ortho.get(&location.add(*axis)).map(|to| (*from, to)))
+        })
+        .collect()
+}
+
+pub fn write(
+    config: &Config,
+    repo: &impl RepoStore,
+    interner: &StringInterner,
+    focus: Option<(usize, usize)>,
+    mut target: impl Write,
+) -> Result<()> {
+    let words: BTreeSet<usize> = match focus {
+        Some((word, hops)) => neighbourhood(config, word, hops),
+        None => config.iter().cloned().collect(),
+    };
+    let mut highlighted: BTreeSet<(usize, usize)> = BTreeSet::default();
+    for word in &words {
+        if let Some(bucket) = repo.find_by_word(*word)? {
+            highlighted.extend(bucket.iter().flat_map(ortho_edges));
+        }
+    }
+
+    let mut nodes = words
+        .iter()
+        .map(|word| Ok((resolve(interner, *word)?, *word)))
+        .collect::<Result<Vec<_>>>()?;
+    nodes.sort();
+    let mut edges = config
+        .edges()
+        .filter(|(from, to)| words.contains(from) && words.contains(to))
+        .map(|(from, to)| {
+            Ok((
+                resolve(interner, from)?,
+                resolve(interner, to)?,
+                highlighted.contains(&(from, to)),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    edges.sort();
+
+    writeln!(target, "digraph config {{")?;
+    for (name, word) in &nodes {
+        if focus.is_some_and(|(focus, _hops)| focus == *word) {
+            writeln!(target, "  {} [style=filled];", quote(name))?;
+        } else {
+            writeln!(target, "  {};", quote(name))?;
+        }
+    }
+    for (from, to, highlighted) in &edges {
+        if *highlighted {
+            writeln!(
+                target,
+                "  {} -> {} [color=red, penwidth=2];",
+                quote(from),
+                quote(to)
+            )?;
+        } else {
+            writeln!(target, "  {} -> {};", quote(from), quote(to))?;
+        }
+    }
+    writeln!(target, "}}")?;
+    Ok(())
+}
+
+fn quote(word: &str) -> String {
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Support;
+    use crate::repo::Repo;
+    use crate::search::make_atoms;
+    use string_interner::Symbol;
+
+    fn folded(text: &str) -> (Config, Repo, StringInterner) {
+        let (config, interner) = Config::from_sentences(text.to_string());
+        let mut repo = Repo::new();
+        make_atoms(&config, Support::default(), &mut repo, 1).unwrap();
+        (config, repo, interner)
+    }
+
+    fn written(text: &str, focus: Option<(&str, usize)>) -> String {
+        let (config, repo, interner) = folded(text);
+        let focus = focus.map(|(word, hops)| (interner.get(word).unwrap().to_usize(), hops));
+        let mut out = vec![];
+        write(&config, &repo, &interner, focus, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn it_highlights_edges_in_orthos() {
+        assert_eq!(
+            written("a b. c d. a c. b d. d e.", None),
+            concat!(
+                "digraph config {\n",
+                "  \"a\";\n  \"b\";\n  \"c\";\n  \"d\";\n  \"e\";\n",
+                "  \"a\" -> \"b\" [color=red, penwidth=2];\n",
+                "  \"a\" -> \"c\" [color=red, penwidth=2];\n",
+                "  \"b\" -> \"d\" [color=red, penwidth=2];\n",
+                "  \"c\" -> \"d\" [color=red, penwidth=2];\n",
+                "  \"d\" -> \"e\";\n",
+                "}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn it_restricts_to_a_neighbourhood() {
+        let text = "a b. c d. a c. b d. d e. e f.";
+        assert_eq!(
+            written(text, Some(("e", 1))),
+            concat!(
+                "digraph config {\n",
+                "  \"d\";\n  \"e\" [style=filled];\n  \"f\";\n",
+                "  \"d\" -> \"e\";\n",
+                "  \"e\" -> \"f\";\n",
+                "}\n"
+            )
+        );
+
+        let (config, _repo, interner) = folded(text);
+        let e = interner.get("e").unwrap().to_usize();
+        assert_eq!(neighbourhood(&config, e, 0).len(), 1);
+        assert_eq!(neighbourhood(&config, e, 2).len(), 5);
+        assert_eq!(neighbourhood(&config, e, 9).len(), 6);
+    }
+
+    #[test]
+    fn it_finds_the_edges_of_an_ortho() {
+        let ortho = Ortho::from_grid(&[2, 3], &[1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(
+            ortho_edges(&ortho),
+            [(1, 2), (2, 3), (4, 5), (5, 6), (1, 4), (2, 5), (3, 6)]
+                .iter()
+                .cloned()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn it_quotes_names() {
+        assert_eq!(quote("say \"hi\\"), "\"say \\\"hi\\\\\"");
+    }
+}
@@ -0,0 +1,716 @@
+use crate::binary;
+use crate::config::{LiteralConfig, Support};
+use crate::disk::DiskRepo;
+use crate::dot;
+use crate::error::{Error, Result};
+use crate::export::{self, Format};
+use crate::journal::{self, Entry};
+use crate::ortho::{lookup, LiteralOrtho};
+use crate::query::Query;
+use crate::render::render;
+use crate::repo::{LiteralRepo, Repo, RepoStats, RepoStore};
+use crate::search::{make_atoms, make_atoms_near};
+use crate::store::{Backend, Store};
+use crate::tokenizer::{Classic, Tokenizer};
+use crate::workspace::{replace, Lock, LockMode, Workspace};
+use crate::Config;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::thread;
+use string_interner::StringInterner;
+use string_interner::Symbol;
+
+pub struct Engine {
+    workspace: Workspace,
+    interner: StringInterner,
+    config: Config,
+    repo: Store,
+    threads: usize,
+    pending: Vec<Entry>,
+    lock: Option<Lock>,
+    tokenizer: Box<dyn Tokenizer>,
+    support: Support,
+}
+
+impl Engine {
+    pub fn new(workspace: Workspace) -> Engine {
+        Engine {
+            workspace,
+            interner: StringInterner::default(),
+            config: Config::new(),
+            repo: Store::Memory(Repo::new()),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            pending: vec![],
+            lock: None,
+            tokenizer: Box::new(Classic),
+            support: Support::default(),
+        }
+    }
+
+    pub fn open(workspace: Workspace) -> Result<Engine> {
+        Self::open_with(workspace, LockMode::Wait)
+    }
+
+    pub fn open_with(workspace: Workspace, mode: LockMode) -> Result<Engine> {
+        workspace.prepare()?;
+        Self::load(workspace, mode)
+    }
+
+    // for commands that only read, where an empty workspace would hide a typo
+    pub fn open_existing(workspace: Workspace, mode: LockMode) -> Result<Engine> {
+        if !workspace.exists() {
+            return Err(Error::Missing(workspace.state));
+        }
+        Self::load(workspace, mode)
+    }
+
+    fn load(workspace: Workspace, mode: LockMode) -> Result<Engine> {
+        let lock = workspace.lock(mode)?;
+        let mut engine = if workspace.state.exists() {
+            let mut engine = Engine::new(workspace);
+            let (interner, config, repo) = binary::load(File::open(&engine.workspace.state)?)?;
+            engine.interner = interner;
+            engine.config = config;
+            engine.repo = Store::Memory(repo);
+            engine
+        } else {
+            Self::read_yaml(workspace)?
+        };
+        if engine.workspace.store_path().exists() {
+            engine.repo = Self::open_disk(&engine.workspace, &engine.repo)?;
+        }
+
+        if engine.workspace.journal_path().exists() {
+            journal::replay(
+                &engine.workspace.journal_path(),
+                &mut engine.interner,
+                &mut engine.config,
+                &mut engine.repo,
+            )?;
+        }
+        engine.lock = Some(lock);
+        Ok(engine)
+    }
+
+    pub fn open_yaml(workspace: Workspace) -> Result<Engine> {
+        workspace.prepare()?;
+        let lock = workspace.lock(LockMode::Wait)?;
+        let mut engine = Self::read_yaml(workspace)?;
+        engine.lock = Some(lock);
+        Ok(engine)
+    }
+
+    // the yaml files are the only input, so converting without them, or over
+    // binary state that may be newer than they are, would lose data
+    pub fn convert(workspace: Workspace, force: bool) -> Result<()> {
+        if !workspace.config.exists() {
+            return Err(Error::Missing(workspace.config));
+        }
+        let mut engine = Self::open_yaml(workspace)?;
+        if !force {
+            for path in [
+                &engine.workspace.state,
+                &engine.workspace.journal_path(),
+                &engine.workspace.store_path(),
+            ] {
+                if path.exists() {
+                    return Err(Error::Exists(path.clone()));
+                }
+            }
+        }
+        if engine.workspace.store_path().exists() {
+            fs::remove_dir_all(engine.workspace.store_path())?;
+        }
+        engine.compact()
+    }
+
+    fn read_yaml(workspace: Workspace) -> Result<Engine> {
+        let mut engine = Engine::new(workspace);
+        if engine.workspace.config.exists() {
+            let literal_config = LiteralConfig::load(File::open(&engine.workspace.config)?)?;
+            let literal_repo = LiteralRepo::load(File::open(&engine.workspace.repo)?)?;
+            engine.config = literal_config.intern(&mut engine.interner);
+            engine.repo = Store::Memory(literal_repo.intern(&engine.interner)?);
+        }
+        Ok(engine)
+    }
+
+    pub fn ingest(&mut self, text: String) -> Result<()> {
+        let first_symbol = self.interner.len();
+        let config = LiteralConfig::new(self.tokenizer.tokenize(&text)).intern(&mut self.interner);
+        let touched: Vec<(usize, usize)> = config.edges().collect();
+        let support = self.support;
+        let admitted: BTreeSet<(usize, usize)> = touched
+            .iter()
+            .cloned()
+            .filter(|(from, to)| support.admits(&self.config, *from, *to))
+            .collect();
+        self.config.merge(config);
+        // an edge that only now meets the support is as new to search as one
+        // never seen before
+        let edges: BTreeSet<(usize, usize)> = touched
+            .iter()
+            .cloned()
+            .filter(|edge| support.admits(&self.config, edge.0, edge.1) && !admitted.contains(edge))
+            .collect();
+        let orthos = make_atoms_near(&self.config, support, &mut self.repo, &edges, self.threads)?;
+        self.record(Entry {
+            first_symbol,
+            symbols: (&self.interner)
+                .into_iter()
+                .skip(first_symbol)
+                .map(|(_symbol, word)| word.to_string())
+                .collect(),
+            edges: touched
+                .into_iter()
+                .map(|(from, to)| (from, to, self.config.count(from, to)))
+                .collect(),
+            orthos,
+        });
+        Ok(())
+    }
+
+    pub fn rescan(&mut self) -> Result<()> {
+        let orthos = make_atoms(&self.config, self.support, &mut self.repo, self.threads)?;
+        self.record(Entry {
+            first_symbol: self.interner.len(),
+            orthos,
+            ..Entry::default()
+        });
+        Ok(())
+    }
+
+    fn record(&mut self, entry: Entry) {
+        if !entry.is_empty() {
+            self.pending.push(entry);
+        }
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn set_support(&mut self, support: Support) {
+        self.support = support;
+    }
+
+    pub fn set_tokenizer(&mut self, tokenizer: Box<dyn Tokenizer>) {
+        self.tokenizer = tokenizer;
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        // the journal only ever sits on top of a binary snapshot
+        if !self.workspace.state.exists() {
+            return self.compact();
+        }
+        journal::append(&self.workspace.journal_path(), &self.pending)?;
+        self.repo.flush()?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    pub fn compact(&mut self) -> Result<()> {
+        self.workspace.prepare()?;
+        // orthos in sled are durable once flushed, so the snapshot leaves them out
+        self.repo.flush()?;
+        let empty = Repo::new();
+        let repo = match &self.repo {
+            Store::Memory(repo) => repo,
+            Store::Disk(_) => &empty,
+        };
+        replace(&self.workspace.state, |target| {
+            binary::save(&self.interner, &self.config, repo, target)
+        })?;
+        if self.workspace.journal_path().exists() {
+            fs::remove_file(self.workspace.journal_path())?;
+        }
+        self.pending.clear();
+        Ok(())
+    }
+
+    pub fn save_yaml(&self) -> Result<()> {
+        self.workspace.prepare()?;
+        let repo = self.repo.unintern(&self.interner)?;
+        let config = self.config.unintern(&self.interner)?;
+        replace(&self.workspace.repo, |target| repo.save(target))?;
+        replace(&self.workspace.config, |target| config.save(target))
+    }
+
+    // moving to sled compacts right away, so the snapshot stops holding orthos
+    // that now live in the store; there is no way back short of a convert
+    pub fn set_backend(&mut self, backend: Backend) -> Result<()> {
+        match (self.repo.backend(), backend) {
+            (Backend::Memory, Backend::Sled) => {
+                self.workspace.prepare()?;
+                self.repo = Self::open_disk(&self.workspace, &self.repo)?;
+                self.compact()
+            }
+            (Backend::Sled, Backend::Memory) => Err(Error::Exists(self.workspace.store_path())),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.repo.backend()
+    }
+
+    // a crash between flushing sled and rewriting the snapshot leaves orthos in
+    // both; adding them again is harmless
+    fn open_disk(workspace: &Workspace, loaded: &Store) -> Result<Store> {
+        let mut disk = DiskRepo::open(workspace.store_path())?;
+        for ortho in loaded.orthos() {
+            disk.add(ortho?.into_owned())?;
+        }
+        Ok(Store::Disk(disk))
+    }
+
+    pub fn stats(&self) -> Result<RepoStats> {
+        self.repo.stats()
+    }
+
+    pub fn query(&self, query: &Query) -> Result<Vec<LiteralOrtho>> {
+        query
+            .run(&self.repo, &self.interner)?
+            .iter()
+            .map(|o| o.unintern(&self.interner))
+            .collect()
+    }
+
+    pub fn show(&self, word: &str) -> Result<Vec<String>> {
+        let bucket = match self.interner.get(word) {
+            Some(symbol) => self.repo.find_by_word(symbol.to_usize())?,
+            None => None,
+        };
+        let mut orthos: Vec<_> = bucket.iter().flat_map(|bucket| bucket.iter()).collect();
+        orthos.sort_by_key(|o| o.shape());
+        orthos
+            .into_iter()
+            .map(|o| render(o, &self.interner))
+            .collect()
+    }
+
+    pub fn export(&self, format: Format, target: impl std::io::Write) -> Result<()> {
+        match format {
+            Format::Yaml => self.repo.unintern(&self.interner)?.save(target),
+            Format::Jsonl => export::jsonl(&export::records(&self.repo, &self.interner)?, target),
+            Format::Csv => export::csv(&export::records(&self.repo, &self.interner)?, target),
+        }
+    }
+
+    pub fn dot(&self, around: Option<(&str, usize)>, target: impl std::io::Write) -> Result<()> {
+        let focus = match around {
+            Some((word, hops)) => Some((lookup(&self.interner, word)?, hops)),
+            None => None,
+        };
+        dot::write(&self.config, &self.repo, &self.interner, focus, target)
+    }
+
+    pub fn workspace(&self) -> &Workspace {
+        &self.workspace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_ingests_repeatedly_in_process() {
+        let mut engine = Engine::new(Workspace::scratch("engine-ingests"));
+        engine
+            .ingest("a b. c d. a c. b d. i k. j l.".to_string())
+            .unwrap();
+        assert_eq!(engine.stats().unwrap().orthos, 1);
+
+        engine
+            .ingest("e f. g h. e g. f h. i j. k l.".to_string())
+            .unwrap();
+        assert_eq!(engine.stats().unwrap().orthos, 3);
+        assert!(!engine.workspace().exists());
+    }
+
+    #[test]
+    fn it_ingests_on_one_thread() {
+        let mut engine = Engine::new(Workspace::scratch("engine-one-thread"));
+        engine.set_threads(0);
+        assert_eq!(engine.threads(), 1);
+
+        engine
+            .ingest("a b. c d. a c. b d. e f. g h. e g. f h.".to_string())
+            .unwrap();
+        engine.rescan().unwrap();
+        assert_eq!(engine.stats().unwrap().orthos, 2);
+    }
+
+    #[test]
+    fn it_ingests_with_another_tokenizer() {
+        let mut engine = Engine::new(Workspace::scratch("engine-tokenizer"));
+        engine.set_tokenizer(Box::new(crate::tokenizer::Lines));
+        engine
+            .ingest("A.M. b\nC.D. d\nA.M. C.D.\nb d".to_string())
+            .unwrap();
+
+        assert_eq!(engine.stats().unwrap().orthos, 1);
+        assert_eq!(engine.show("A.M.").unwrap(), vec!["A.M. C.D.\nb    d"]);
+    }
+
+    #[test]
+    fn it_searches_only_edges_with_support() {
+        let mut engine = Engine::new(Workspace::scratch("engine-support"));
+        engine.set_support(Support::Count(2));
+        engine
+            .ingest("a b. c d. a c. b d. e f. g h. e g. f h.".to_string())
+            .unwrap();
+        assert_eq!(engine.stats().unwrap().orthos, 0);
+
+        engine
+            .ingest("a b. c d. a c. b d. e f.".to_string())
+            .unwrap();
+        assert_eq!(engine.show("a").unwrap(), vec!["a b\nc d"]);
+        assert!(engine.show("e").unwrap().is_empty());
+
+        let id = |word| engine.interner.get(word).unwrap().to_usize();
+        assert_eq!(engine.config.count(id("g"), id("h")), 1);
+        engine.set_support(Support::default());
+        engine.rescan().unwrap();
+        assert_eq!(engine.stats().unwrap().orthos, 2);
+    }
+
+    #[test]
+    fn it_shows_orthos_for_a_word() {
+        let mut engine = Engine::new(Workspace::scratch("engine-shows"));
+        engine
+            .ingest("a b e. c d f. a c. b d. e f.".to_string())
+            .unwrap();
+
+        assert_eq!(
+            engine.show("f").unwrap(),
+            vec!["b d\ne f".to_string(), "a b e\nc d f".to_string()]
+        );
+        assert!(engine.show("zebra").unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_draws_a_word_neighbourhood() {
+        let mut engine = Engine::new(Workspace::scratch("engine-dot"));
+        engine
+            .ingest("a b. c d. a c. b d. d e.".to_string())
+            .unwrap();
+
+        let mut out = vec![];
+        engine.dot(Some(("e", 1)), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "digraph config {\n  \"d\";\n  \"e\" [style=filled];\n  \"d\" -> \"e\";\n}\n"
+        );
+        assert!(matches!(
+            engine.dot(Some(("zebra", 1)), vec![]),
+            Err(Error::UnknownSymbol(_))
+        ));
+    }
+
+    #[test]
+    fn it_saves_and_reopens() {
+        let workspace = Workspace::scratch("engine-reopens");
+        let mut engine = Engine::open(workspace.clone()).unwrap();
+        engine.ingest("a b. c d. a c. b d.".to_string()).unwrap();
+        engine.save().unwrap();
+        drop(engine.lock.take());
+
+        let mut reopened = Engine::open(workspace.clone()).unwrap();
+        assert_eq!(reopened.stats().unwrap(), engine.stats().unwrap());
+
+        reopened.ingest("a e. e f. c f.".to_string()).unwrap();
+        engine.ingest("a e. e f. c f.".to_string()).unwrap();
+        assert_eq!(reopened.stats().unwrap().orthos, 2);
+        assert_eq!(
+            reopened.query(&Query::default()).unwrap(),
+            engine.query(&Query::default()).unwrap()
+        );
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_converts_yaml_state() {
+        let workspace = Workspace::scratch("engine-converts");
+        let mut engine = Engine::new(workspace.clone());
+        engine
+            .ingest("a b e. c d f. a c. b d. e f.".to_string())
+            .unwrap();
+        engine.save_yaml().unwrap();
+        assert!(!workspace.state.exists());
+
+        let mut opened = Engine::open(workspace.clone()).unwrap();
+        assert_eq!(opened.stats().unwrap(), engine.stats().unwrap());
+        opened.save().unwrap();
+        drop(opened);
+        assert!(workspace.state.exists());
+
+        let reopened = Engine::open(workspace.clone()).unwrap();
+        assert_eq!(reopened.stats().unwrap(), engine.stats().unwrap());
+        assert_eq!(
+            reopened.repo.unintern(&reopened.interner).unwrap(),
+            engine.repo.unintern(&engine.interner).unwrap()
+        );
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_keeps_orthos_in_sled_once_asked() {
+        let workspace = Workspace::scratch("engine-sled");
+        let mut engine = Engine::open(workspace.clone()).unwrap();
+        engine.ingest("a b. c d. a c. b d.".to_string()).unwrap();
+        engine.save().unwrap();
+        engine.set_backend(Backend::Sled).unwrap();
+        assert!(workspace.store_path().exists());
+        let (_interner, _config, snapshot) =
+            binary::load(File::open(&workspace.state).unwrap()).unwrap();
+        assert!(snapshot.is_empty());
+
+        engine.ingest("e f. g h. e g. f h.".to_string()).unwrap();
+        engine.save().unwrap();
+        drop(engine);
+
+        let mut reopened = Engine::open(workspace.clone()).unwrap();
+        assert_eq!(reopened.backend(), Backend::Sled);
+        assert_eq!(reopened.stats().unwrap().orthos, 2);
+        assert_eq!(reopened.show("e").unwrap(), vec!["e f\ng h"]);
+        assert!(matches!(
+            reopened.set_backend(Backend::Memory),
+            Err(Error::Exists(_))
+        ));
+        reopened.compact().unwrap();
+        drop(reopened);
+        assert_eq!(
+            Engine::open(workspace.clone())
+                .unwrap()
+                .stats()
+                .unwrap()
+                .orthos,
+            2
+        );
+
+        workspace.reset().unwrap();
+        assert!(!workspace.store_path().exists());
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_reads_only_existing_workspaces() {
+        let workspace = Workspace::scratch("engine-existing");
+        assert!(matches!(
+            Engine::open_existing(workspace.clone(), LockMode::Fail),
+            Err(Error::Missing(_))
+        ));
+        assert!(!workspace.state.parent().unwrap().exists());
+
+        let mut engine = Engine::open(workspace.clone()).unwrap();
+        engine.ingest("a b. c d. a c. b d.".to_string()).unwrap();
+        engine.save().unwrap();
+        drop(engine);
+        let opened = Engine::open_existing(workspace.clone(), LockMode::Fail).unwrap();
+        assert_eq!(opened.stats().unwrap().orthos, 1);
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_converts_only_when_nothing_would_be_lost() {
+        let workspace = Workspace::scratch("engine-convert-guard");
+        assert!(matches!(
+            Engine::convert(workspace.clone(), false),
+            Err(Error::Missing(_))
+        ));
+
+        let mut engine = Engine::new(workspace.clone());
+        engine.ingest("a b. c d. a c. b d.".to_string()).unwrap();
+        engine.save_yaml().unwrap();
+        Engine::convert(workspace.clone(), false).unwrap();
+        assert!(matches!(
+            Engine::convert(workspace.clone(), false),
+            Err(Error::Exists(_))
+        ));
+
+        let mut newer = Engine::open(workspace.clone()).unwrap();
+        newer.ingest("a e. e f. c f.".to_string()).unwrap();
+        newer.save().unwrap();
+        drop(newer);
+        assert!(matches!(
+            Engine::convert(workspace.clone(), false),
+            Err(Error::Exists(_))
+        ));
+        assert_eq!(
+            Engine::open(workspace.clone())
+                .unwrap()
+                .stats()
+                .unwrap()
+                .orthos,
+            2
+        );
+
+        Engine::convert(workspace.clone(), true).unwrap();
+        assert_eq!(
+            Engine::open(workspace.clone())
+                .unwrap()
+                .stats()
+                .unwrap()
+                .orthos,
+            1
+        );
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_journals_saves_after_the_first() {
+        let workspace = Workspace::scratch("engine-journals");
+        let mut engine = Engine::open(workspace.clone()).unwrap();
+        engine.ingest("a b. c d. a c. b d.".to_string()).unwrap();
+        engine.save().unwrap();
+        assert!(workspace.state.exists());
+        assert!(!workspace.journal_path().exists());
+        let snapshot = fs::read(&workspace.state).unwrap();
+
+        engine.ingest("a e. e f. c f.".to_string()).unwrap();
+        engine.save().unwrap();
+        engine.ingest("b g. d h. g h.".to_string()).unwrap();
+        engine.save().unwrap();
+        assert_eq!(fs::read(&workspace.state).unwrap(), snapshot);
+        assert!(workspace.journal_path().exists());
+        drop(engine.lock.take());
+
+        let mut reopened = Engine::open(workspace.clone()).unwrap();
+        assert_eq!(reopened.stats().unwrap(), engine.stats().unwrap());
+        assert_eq!(
+            reopened.repo.unintern(&reopened.interner).unwrap(),
+            engine.repo.unintern(&engine.interner).unwrap()
+        );
+
+        reopened.compact().unwrap();
+        drop(reopened);
+        assert!(!workspace.journal_path().exists());
+        let compacted = Engine::open(workspace.clone()).unwrap();
+        assert_eq!(
+            compacted.repo.unintern(&compacted.interner).unwrap(),
+            engine.repo.unintern(&engine.interner).unwrap()
+        );
+        assert_eq!(compacted.interner, engine.interner);
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_keeps_edge_counts_across_saves() {
+        let workspace = Workspace::scratch("engine-counts");
+        let mut engine = Engine::open(workspace.clone()).unwrap();
+        for text in ["a b. c d.", "a b. a c.", "a b. b d."] {
+            engine.ingest(text.to_string()).unwrap();
+            engine.save().unwrap();
+        }
+        drop(engine.lock.take());
+
+        let mut reopened = Engine::open(workspace.clone()).unwrap();
+        let id = |word| reopened.interner.get(word).unwrap().to_usize();
+        let (a, b) = (id("a"), id("b"));
+        assert_eq!(reopened.config.count(a, b), 3);
+        reopened.compact().unwrap();
+        drop(reopened);
+
+        let compacted = Engine::open(workspace.clone()).unwrap();
+        workspace.clean();
+        assert_eq!(compacted.config.count(a, b), 3);
+        assert_eq!(
+            compacted.config.unintern(&compacted.interner).unwrap(),
+            engine.config.unintern(&engine.interner).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_keeps_a_journal_per_state_file() {
+        let first = Workspace::scratch("engine-two-states");
+        let mut second = first.clone();
+        second.state = first.state.with_file_name("other.bin");
+        assert_ne!(first.journal_path(), second.journal_path());
+
+        let mut engines = [
+            Engine::open(first.clone()).unwrap(),
+            Engine::open(second.clone()).unwrap(),
+        ];
+        for (engine, texts) in engines
+            .iter_mut()
+            .zip([["a b. c d.", "a c. b d."], ["e f. g h.", "e g. f h."]])
+        {
+            for text in texts {
+                engine.ingest(text.to_string()).unwrap();
+                engine.save().unwrap();
+            }
+        }
+        assert!(first.journal_path().exists());
+        assert!(second.journal_path().exists());
+        let expected: Vec<_> = engines
+            .iter()
+            .map(|engine| engine.repo.unintern(&engine.interner).unwrap())
+            .collect();
+        drop(engines);
+
+        for (workspace, expected) in [first.clone(), second].iter().zip(expected) {
+            let reopened = Engine::open(workspace.clone()).unwrap();
+            assert_eq!(reopened.stats().unwrap().orthos, 1);
+            assert_eq!(
+                reopened.repo.unintern(&reopened.interner).unwrap(),
+                expected
+            );
+        }
+        first.clean();
+    }
+
+    #[test]
+    fn it_fails_fast_while_another_run_holds_the_workspace() {
+        let workspace = Workspace::scratch("engine-locked");
+        let held = Engine::open(workspace.clone()).unwrap();
+        assert!(matches!(
+            Engine::open_with(workspace.clone(), LockMode::Fail),
+            Err(Error::Locked(_))
+        ));
+
+        drop(held);
+        Engine::open_with(workspace.clone(), LockMode::Fail).unwrap();
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_serializes_concurrent_ingests() {
+        let workspace = Workspace::scratch("engine-concurrent");
+        let texts: Vec<String> = (0..8)
+            .map(|i| format!("a{0} b{0}. c{0} d{0}. a{0} c{0}. b{0} d{0}. a{0} x.", i))
+            .collect();
+
+        thread::scope(|scope| {
+            for text in &texts {
+                let workspace = workspace.clone();
+                scope.spawn(move || {
+                    let mut engine = Engine::open(workspace).unwrap();
+                    engine.ingest(text.clone()).unwrap();
+                    engine.save().unwrap();
+                });
+            }
+        });
+
+        let mut expected = Engine::new(Workspace::scratch("engine-concurrent-expected"));
+        for text in &texts {
+            expected.ingest(text.clone()).unwrap();
+        }
+        let opened = Engine::open(workspace.clone()).unwrap();
+        assert_eq!(opened.stats().unwrap().orthos, 8);
+        assert_eq!(
+            opened.repo.unintern(&opened.interner).unwrap(),
+            expected.repo.unintern(&expected.interner).unwrap()
+        );
+        assert_eq!(
+            opened.config.unintern(&opened.interner).unwrap(),
+            expected.config.unintern(&expected.interner).unwrap()
+        );
+        workspace.clean();
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+    Json(serde_json::Error),
+    UnknownSymbol(String),
+    InconsistentRepo(String),
+    Format(String),
+    Locked(std::path::PathBuf),
+    Missing(std::path::PathBuf),
+    Exists(std::path::PathBuf),
+    Store(sled::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "i/o error: {}", e),
+            Error::Yaml(e) => write!(f, "yaml error: {}", e),
+            Error::Json(e) => write!(f, "json error: {}", e),
+            Error::UnknownSymbol(symbol) => write!(f, "unknown symbol: {}", symbol),
+            Error::InconsistentRepo(reason) => write!(f, "inconsistent repo: {}", reason),
+            Error::Format(reason) => write!(f, "format error: {}", reason),
+            Error::Store(e) => write!(f, "repo store error: {}", e),
+            Error::Locked(path) => write!(
+                f,
+                "workspace is locked by another run (lock file {})",
+                path.display()
+            ),
+            Error::Missing(path) => write!(f, "{} does not exist", path.display()),
+            Error::Exists(path) => write!(f, "{} already exists", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Yaml(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Store(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(e: serde_yaml::Error) -> Self {
+        Error::Yaml(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<sled::Error> for Error {
+    fn from(e: sled::Error) -> Self {
+        Error::Store(e)
+    }
+}
@@ -1,22 +1,31 @@
-use crate::repo::Repo;
+use crate::config::Support;
+use crate::error::Result;
+use crate::repo::RepoStore;
 use crate::{Config, Ortho};
 use std::collections::BTreeSet;
 
-pub fn create(config: &Config, repo: &Repo, a: usize) -> BTreeSet<Ortho> {
+pub fn create(
+    config: &Config,
+    support: Support,
+    repo: &impl RepoStore,
+    a: usize,
+) -> Result<BTreeSet<Ortho>> {
     let mut results = BTreeSet::default();
     // a -> b -> d <- c <- a'
     // a == a'
     // b != c
 
-    if let Some(potential_bs) = config.project_forward(a) {
-        for b in potential_bs {
-            if let Some(potential_ds) = config.project_forward(*b) {
-                for d in potential_ds {
-                    if let Some(potential_cs) = config.project_backward(*d) {
-                        for c in potential_cs {
+    if let Some(potential_bs) = config.project_forward_supported(a, support) {
+        for b in potential_bs.iter() {
+            if let Some(potential_ds) = config.project_forward_supported(*b, support) {
+                for d in potential_ds.iter() {
+                    if let Some(potential_cs) = config.project_backward_supported(*d, support) {
+                        for c in potential_cs.iter() {
                             if b != c {
-                                if let Some(potential_a_primes) = config.project_backward(*c) {
-                                    for a_prime in potential_a_primes {
+                                if let Some(potential_a_primes) =
+                                    config.project_backward_supported(*c, support)
+                                {
+                                    for a_prime in potential_a_primes.iter() {
                                         if a == *a_prime {
                                             results.insert(Ortho::new(a, *b, *c, *d));
                                         }
@@ -35,12 +44,19 @@ pub fn create(config: &Config, repo: &Repo, a: usize) -> BTreeSet<Ortho> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::repo::Repo;
     use string_interner::Symbol;
     #[test]
     fn it_can_be_made() {
         let (config, interner) = Config::from_sentences("a b. c d. a c. b d.".to_string());
         let repo = Repo::new();
-        let res = create(&config, &repo, interner.get("a").unwrap().to_usize());
+        let res = create(
+            &config,
+            Support::default(),
+            &repo,
+            interner.get("a").unwrap().to_usize(),
+        )
+        .unwrap();
         assert!(res.len() == 1);
     }
 
@@ -48,14 +64,52 @@ mod tests {
     fn it_will_not_return_the_same_thing_twice() {
         let (config, interner) = Config::from_sentences("a b. c d. a c. b d.".to_string());
         let mut repo = Repo::new();
-        let res = create(&config, &repo, interner.get("a").unwrap().to_usize());
+        let res = create(
+            &config,
+            Support::default(),
+            &repo,
+            interner.get("a").unwrap().to_usize(),
+        )
+        .unwrap();
         assert!(res.len() == 1);
 
         res.iter().for_each(|x| {
-            repo.add(x.clone());
+            repo.add(x.clone()).unwrap();
         });
 
-        let res = create(&config, &repo, interner.get("a").unwrap().to_usize());
-        assert!(res.len() == 0);
+        let res = create(
+            &config,
+            Support::default(),
+            &repo,
+            interner.get("a").unwrap().to_usize(),
+        )
+        .unwrap();
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn it_finds_every_square_sharing_an_origin() {
+        let (config, interner) =
+            Config::from_sentences("a b. c d. a c. b d. a e. e f. c f.".to_string());
+        let mut repo = Repo::new();
+        let res = create(
+            &config,
+            Support::default(),
+            &repo,
+            interner.get("a").unwrap().to_usize(),
+        )
+        .unwrap();
+        assert_eq!(res.len(), 2);
+
+        repo.add(res.iter().next().unwrap().clone()).unwrap();
+
+        let res = create(
+            &config,
+            Support::default(),
+            &repo,
+            interner.get("a").unwrap().to_usize(),
+        )
+        .unwrap();
+        assert_eq!(res.len(), 1);
     }
 }
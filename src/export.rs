@@ -0,0 +1,186 @@
+use crate::error::{Error, Result};
+use crate::ortho::{resolve, Ortho};
+use crate::render::{axes, coordinates};
+use crate::repo::RepoStore;
+use serde::Serialize;
+use std::io::Write;
+use std::str::FromStr;
+use string_interner::StringInterner;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Jsonl,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Format> {
+        match name {
+            "yaml" => Ok(Format::Yaml),
+            "jsonl" => Ok(Format::Jsonl),
+            "csv" => Ok(Format::Csv),
+            other => Err(Error::Format(format!("unknown export format {}", other))),
+        }
+    }
+}
+
+// shape, axes and coordinates run shortest axis first, like Ortho::shape
+// and query --at
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Record {
+    pub shape: Vec<usize>,
+    pub axes: Vec<String>,
+    pub origin: String,
+    pub cells: Vec<Cell>,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Cell {
+    pub at: Vec<usize>,
+    pub word: String,
+}
+
+impl Record {
+    pub fn new(ortho: &Ortho, interner: &StringInterner) -> Result<Record> {
+        let mut cells = ortho
+            .cells()
+            .map(|(location, word)| {
+                Ok(Cell {
+                    at: coordinates(ortho, location, interner)?,
+                    word: resolve(interner, *word)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        cells.sort();
+        Ok(Record {
+            shape: ortho.shape(),
+            origin: resolve(interner, ortho.origin())?,
+            axes: axes(ortho, interner)?
+                .into_iter()
+                .rev()
+                .map(|(word, _axis, _length)| word)
+                .collect(),
+            cells,
+        })
+    }
+}
+
+// the repo indexes every ortho several times over; this walks each one once
+// and sorts, so exports of the same repo are byte for byte identical
+pub fn records(repo: &impl RepoStore, interner: &StringInterner) -> Result<Vec<Record>> {
+    let mut records = repo
+        .orthos()
+        .map(|ortho| ortho.and_then(|ortho| Record::new(&ortho, interner)))
+        .collect::<Result<Vec<_>>>()?;
+    records.sort();
+    Ok(records)
+}
+
+pub fn jsonl(records: &[Record], mut target: impl Write) -> Result<()> {
+    for record in records {
+        serde_json::to_writer(&mut target, record)?;
+        target.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+// one row per 2x2, read row by row: the origin, the hops and the far corner
+pub fn csv(records: &[Record], mut target: impl Write) -> Result<()> {
+    writeln!(target, "a,b,c,d")?;
+    for record in records.iter().filter(|record| record.shape == [2, 2]) {
+        let row: Vec<String> = [[0, 0], [0, 1], [1, 0], [1, 1]]
+            .iter()
+            .filter_map(|at| record.cells.iter().find(|cell| cell.at == at))
+            .map(|cell| field(&cell.word))
+            .collect();
+        writeln!(target, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+fn field(word: &str) -> String {
+    if word.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", word.replace('"', "\"\""))
+    } else {
+        word.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::{Repo, RepoStore};
+    use string_interner::Symbol;
+
+    fn repo(interner: &mut StringInterner, grids: &[(&[usize], &[&str])]) -> Repo {
+        let mut repo = Repo::new();
+        for (shape, words) in grids {
+            let cells: Vec<usize> = words
+                .iter()
+                .map(|word| interner.get_or_intern(word).to_usize())
+                .collect();
+            repo.add(Ortho::from_grid(shape, &cells).unwrap()).unwrap();
+        }
+        repo
+    }
+
+    fn exported(
+        write: impl Fn(&[Record], &mut Vec<u8>) -> Result<()>,
+        grids: &[(&[usize], &[&str])],
+    ) -> String {
+        let mut interner = StringInterner::default();
+        let repo = repo(&mut interner, grids);
+        let mut out = vec![];
+        write(&records(&repo, &interner).unwrap(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn it_writes_each_ortho_once_as_json_lines() {
+        let out = exported(
+            |records, out| jsonl(records, out),
+            &[
+                (&[2, 2], &["a", "b", "c", "d"]),
+                (&[2, 3], &["a", "b", "e", "c", "d", "f"]),
+            ],
+        );
+        assert_eq!(
+            out,
+            concat!(
+                r#"{"shape":[2,2],"axes":["c","b"],"origin":"a","cells":["#,
+                r#"{"at":[0,0],"word":"a"},{"at":[0,1],"word":"b"},"#,
+                r#"{"at":[1,0],"word":"c"},{"at":[1,1],"word":"d"}]}"#,
+                "\n",
+                r#"{"shape":[2,3],"axes":["c","b"],"origin":"a","cells":["#,
+                r#"{"at":[0,0],"word":"a"},{"at":[0,1],"word":"b"},"#,
+                r#"{"at":[0,2],"word":"e"},{"at":[1,0],"word":"c"},"#,
+                r#"{"at":[1,1],"word":"d"},{"at":[1,2],"word":"f"}]}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn it_writes_squares_as_csv_rows() {
+        let out = exported(
+            |records, out| csv(records, out),
+            &[
+                (&[2, 2], &["a", "b", "c", "d"]),
+                (&[2, 2], &["x,y", "say \"hi\"", "p", "q"]),
+                (&[2, 3], &["a", "b", "e", "c", "d", "f"]),
+            ],
+        );
+        assert_eq!(out, "a,b,c,d\na,b,c,d\n\"x,y\",p,\"say \"\"hi\"\"\",q\n");
+    }
+
+    #[test]
+    fn it_parses_format_names() {
+        assert_eq!("jsonl".parse::<Format>().unwrap(), Format::Jsonl);
+        assert_eq!("csv".parse::<Format>().unwrap(), Format::Csv);
+        assert_eq!("yaml".parse::<Format>().unwrap(), Format::Yaml);
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
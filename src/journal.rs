@@ -0,0 +1,396 @@
+use crate::binary::{decode, encode, write_varint, Reader};
+use crate::error::{Error, Result};
+use crate::repo::RepoStore;
+use crate::workspace;
+use crate::{Config, Ortho};
+use std::convert::TryInto;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use string_interner::StringInterner;
+use string_interner::Symbol;
+
+const MAGIC: &[u8; 4] = b"MFLJ";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub first_symbol: usize,
+    pub symbols: Vec<String>,
+    // every edge the entry touched with its count afterwards, so replaying
+    // an entry twice cannot count an edge twice
+    pub edges: Vec<(usize, usize, usize)>,
+    pub orthos: Vec<Ortho>,
+}
+
+impl Entry {
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty() && self.edges.is_empty() && self.orthos.is_empty()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        write_varint(&mut buffer, self.first_symbol);
+        write_varint(&mut buffer, self.symbols.len());
+        for word in &self.symbols {
+            write_varint(&mut buffer, word.len());
+            buffer.extend_from_slice(word.as_bytes());
+        }
+        write_varint(&mut buffer, self.edges.len());
+        for (from, to, count) in &self.edges {
+            write_varint(&mut buffer, *from);
+            write_varint(&mut buffer, *to);
+            write_varint(&mut buffer, *count);
+        }
+        write_varint(&mut buffer, self.orthos.len());
+        for ortho in &self.orthos {
+            encode(&mut buffer, ortho);
+        }
+        buffer
+    }
+
+    fn decode(payload: &[u8]) -> Result<Entry> {
+        let mut reader = Reader::new(payload);
+        let mut entry = Entry {
+            first_symbol: reader.varint()?,
+            ..Entry::default()
+        };
+        for _ in 0..reader.varint()? {
+            let length = reader.varint()?;
+            let word = std::str::from_utf8(reader.take(length)?)
+                .map_err(|_| Error::Format("word is not utf-8".to_string()))?;
+            entry.symbols.push(word.to_string());
+        }
+        for _ in 0..reader.varint()? {
+            let edge = (reader.varint()?, reader.varint()?, reader.varint()?);
+            entry.edges.push(edge);
+        }
+        for _ in 0..reader.varint()? {
+            entry.orthos.push(decode(&mut reader, Ok)?);
+        }
+        if !reader.is_empty() {
+            return Err(Error::Format(
+                "trailing bytes in journal record".to_string(),
+            ));
+        }
+        Ok(entry)
+    }
+
+    // replaying an entry that is already folded into the snapshot changes nothing
+    fn apply(
+        &self,
+        interner: &mut StringInterner,
+        config: &mut Config,
+        repo: &mut impl RepoStore,
+    ) -> Result<()> {
+        if self.first_symbol > interner.len() {
+            return Err(Error::Format("journal skips symbols".to_string()));
+        }
+        for (offset, word) in self.symbols.iter().enumerate() {
+            if interner.get_or_intern(word).to_usize() != self.first_symbol + offset {
+                return Err(Error::Format(format!(
+                    "journal disagrees on symbol {}",
+                    word
+                )));
+            }
+        }
+
+        let known = |symbol: usize| {
+            if symbol < interner.len() {
+                Ok(symbol)
+            } else {
+                Err(Error::UnknownSymbol(format!("#{}", symbol)))
+            }
+        };
+        for (from, to, count) in &self.edges {
+            config.set_count(known(*from)?, known(*to)?, *count);
+        }
+        for ortho in &self.orthos {
+            for (_location, word) in ortho.cells() {
+                known(*word)?;
+            }
+            repo.add(ortho.clone())?;
+        }
+        Ok(())
+    }
+}
+
+// each record is a little-endian length and fnv-1a checksum followed by an entry
+pub fn append(path: &Path, entries: &[Entry]) -> Result<()> {
+    let mut buffer = vec![];
+    for entry in entries {
+        let payload = entry.encode();
+        buffer.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(&checksum(&payload).to_le_bytes());
+        buffer.extend_from_slice(&payload);
+    }
+
+    let created = !path.exists();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() == 0 {
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        buffer.splice(0..0, header);
+    }
+    file.write_all(&buffer)?;
+    file.sync_data()?;
+    if created {
+        workspace::sync_parent(path)?;
+    }
+    Ok(())
+}
+
+pub fn replay(
+    path: &Path,
+    interner: &mut StringInterner,
+    config: &mut Config,
+    repo: &mut impl RepoStore,
+) -> Result<usize> {
+    let (entries, end) = read(path)?;
+    // appends go to the end of the file, so a record torn by a crash is cut
+    // off before anything can follow it
+    if (end as u64) < fs::metadata(path)?.len() {
+        let file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(end as u64)?;
+        file.sync_data()?;
+    }
+    for entry in &entries {
+        entry.apply(interner, config, repo)?;
+    }
+    Ok(entries.len())
+}
+
+fn header(reader: &mut Reader) -> Result<()> {
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(Error::Format("not a model-fold journal".to_string()));
+    }
+    let version = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+    if version != VERSION {
+        return Err(Error::Format(format!(
+            "unsupported journal version {}",
+            version
+        )));
+    }
+    Ok(())
+}
+
+// every whole record, and where the last of them ends
+fn read(path: &Path) -> Result<(Vec<Entry>, usize)> {
+    let mut contents = vec![];
+    File::open(path)?.read_to_end(&mut contents)?;
+    let mut current = MAGIC.to_vec();
+    current.extend_from_slice(&VERSION.to_le_bytes());
+    if current.starts_with(&contents) && contents.len() < current.len() {
+        return Ok((vec![], 0));
+    }
+
+    let mut reader = Reader::new(&contents);
+    header(&mut reader)?;
+
+    let mut entries = vec![];
+    let mut end = reader.position();
+    while !reader.is_empty() {
+        // a record cut short by a crash can only be the last one
+        let record = reader.take(8).and_then(|header| {
+            let length = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+            let expected = u32::from_le_bytes(header[4..].try_into().unwrap());
+            Ok((expected, reader.take(length)?))
+        });
+        let (expected, payload) = match record {
+            Ok(record) => record,
+            Err(_) => break,
+        };
+        if checksum(payload) != expected {
+            if reader.is_empty() {
+                break;
+            }
+            return Err(Error::Format(
+                "journal record fails its checksum".to_string(),
+            ));
+        }
+        entries.push(Entry::decode(payload)?);
+        end = reader.position();
+    }
+    Ok((entries, end))
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{LiteralConfig, Support};
+    use crate::repo::Repo;
+    use crate::search::make_atoms_near;
+    use crate::workspace::Workspace;
+
+    fn ingest(
+        text: &str,
+        interner: &mut StringInterner,
+        config: &mut Config,
+        repo: &mut Repo,
+    ) -> Entry {
+        let first_symbol = interner.len();
+        let ingested = LiteralConfig::from_raw(text.to_string()).intern(interner);
+        let touched: Vec<(usize, usize)> = ingested.edges().collect();
+        let edges = config.merge(ingested);
+        let orthos = make_atoms_near(config, Support::default(), repo, &edges, 1).unwrap();
+        Entry {
+            first_symbol,
+            symbols: (first_symbol..interner.len())
+                .map(|symbol| crate::ortho::resolve(interner, symbol).unwrap())
+                .collect(),
+            edges: touched
+                .into_iter()
+                .map(|(from, to)| (from, to, config.count(from, to)))
+                .collect(),
+            orthos,
+        }
+    }
+
+    fn written(name: &str) -> (Workspace, StringInterner, Config, Repo) {
+        let workspace = Workspace::scratch(name);
+        workspace.prepare().unwrap();
+        let mut interner = StringInterner::default();
+        let mut config = Config::new();
+        let mut repo = Repo::new();
+        for text in ["a b. c d. a c. b d.", "a e. e f. c f.", "b g. d h. g h."] {
+            let entry = ingest(text, &mut interner, &mut config, &mut repo);
+            append(&workspace.journal_path(), &[entry]).unwrap();
+        }
+        (workspace, interner, config, repo)
+    }
+
+    fn replayed(workspace: &Workspace) -> Result<(StringInterner, Config, Repo)> {
+        let mut interner = StringInterner::default();
+        let mut config = Config::new();
+        let mut repo = Repo::new();
+        replay(
+            &workspace.journal_path(),
+            &mut interner,
+            &mut config,
+            &mut repo,
+        )?;
+        Ok((interner, config, repo))
+    }
+
+    #[test]
+    fn it_replays_ingests() {
+        let (workspace, interner, config, repo) = written("journal-replays");
+        let (replayed_interner, replayed_config, replayed_repo) = replayed(&workspace).unwrap();
+        workspace.clean();
+
+        assert_eq!(repo.len(), 4);
+        assert_eq!(replayed_repo, repo);
+        assert_eq!(replayed_interner, interner);
+        assert_eq!(
+            replayed_config.unintern(&replayed_interner).unwrap(),
+            config.unintern(&interner).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_replays_over_state_it_already_holds() {
+        let (workspace, mut interner, mut config, mut repo) = written("journal-idempotent");
+        let count = replay(
+            &workspace.journal_path(),
+            &mut interner,
+            &mut config,
+            &mut repo,
+        )
+        .unwrap();
+        let (_replayed_interner, _replayed_config, replayed_repo) = replayed(&workspace).unwrap();
+        workspace.clean();
+
+        assert_eq!(count, 3);
+        assert_eq!(replayed_repo, repo);
+    }
+
+    #[test]
+    fn it_replays_edge_counts() {
+        let workspace = Workspace::scratch("journal-counts");
+        workspace.prepare().unwrap();
+        let mut interner = StringInterner::default();
+        let mut config = Config::new();
+        let mut repo = Repo::new();
+        for text in ["a b. a b.", "a b. b c.", "a b."] {
+            let entry = ingest(text, &mut interner, &mut config, &mut repo);
+            append(&workspace.journal_path(), &[entry]).unwrap();
+        }
+        let (replayed_interner, mut replayed_config, mut replayed_repo) =
+            replayed(&workspace).unwrap();
+        replay(
+            &workspace.journal_path(),
+            &mut interner.clone(),
+            &mut replayed_config,
+            &mut replayed_repo,
+        )
+        .unwrap();
+        workspace.clean();
+
+        let id = |word| replayed_interner.get(word).unwrap().to_usize();
+        assert_eq!(replayed_config.count(id("a"), id("b")), 4);
+        assert_eq!(replayed_config.count(id("b"), id("c")), 1);
+        assert_eq!(
+            replayed_config.unintern(&replayed_interner).unwrap(),
+            config.unintern(&interner).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_drops_a_torn_last_record() {
+        let (workspace, _interner, _config, _repo) = written("journal-torn");
+        let contents = std::fs::read(workspace.journal_path()).unwrap();
+        std::fs::write(workspace.journal_path(), &contents[..contents.len() - 3]).unwrap();
+
+        let (_interner, _config, repo) = replayed(&workspace).unwrap();
+        workspace.clean();
+        assert_eq!(repo.len(), 2);
+    }
+
+    #[test]
+    fn it_appends_after_a_torn_record() {
+        let (workspace, _interner, _config, _repo) = written("journal-torn-append");
+        let contents = std::fs::read(workspace.journal_path()).unwrap();
+        std::fs::write(workspace.journal_path(), &contents[..contents.len() - 3]).unwrap();
+
+        let (mut interner, mut config, mut repo) = replayed(&workspace).unwrap();
+        let entry = ingest("b i. d j. i j.", &mut interner, &mut config, &mut repo);
+        append(&workspace.journal_path(), &[entry]).unwrap();
+
+        let reopened = replayed(&workspace);
+        workspace.clean();
+        let (_interner, _config, reopened) = reopened.unwrap();
+        assert_eq!(repo.len(), 4);
+        assert_eq!(reopened, repo);
+    }
+
+    #[test]
+    fn it_reads_a_journal_torn_inside_its_header() {
+        let workspace = Workspace::scratch("journal-torn-header");
+        workspace.prepare().unwrap();
+        std::fs::write(workspace.journal_path(), &MAGIC[..2]).unwrap();
+
+        let (_interner, _config, repo) = replayed(&workspace).unwrap();
+        let length = std::fs::metadata(workspace.journal_path()).unwrap().len();
+        workspace.clean();
+        assert!(repo.is_empty());
+        assert_eq!(length, 0);
+    }
+
+    #[test]
+    fn it_rejects_a_corrupt_record_before_the_end() {
+        let (workspace, _interner, _config, _repo) = written("journal-corrupt");
+        let mut contents = std::fs::read(workspace.journal_path()).unwrap();
+        contents[MAGIC.len() + 4 + 8] ^= 0xff;
+        std::fs::write(workspace.journal_path(), &contents).unwrap();
+
+        let res = replayed(&workspace);
+        workspace.clean();
+        assert!(matches!(res, Err(Error::Format(_))));
+    }
+}
@@ -1,19 +1,77 @@
+mod binary;
 mod config;
+mod disk;
+mod dot;
+mod engine;
+mod error;
 mod ex_nihilo;
+mod export;
+mod journal;
 use std::fs;
 
-pub use config::Config;
+pub use config::{Config, Support};
+pub use disk::DiskRepo;
+pub use engine::Engine;
+pub use error::{Error, Result};
+pub use export::Format;
 
 mod ortho;
+mod over;
+mod query;
+mod render;
 mod repo;
 mod search;
+mod segmenter;
+mod store;
+mod tokenizer;
+mod up;
+mod workspace;
 
-pub use ortho::Ortho;
+pub use ortho::{LiteralOrtho, MultiSet, Ortho};
+pub use query::Query;
+pub use repo::{Repo, RepoStats, RepoStore};
+pub use search::{make_atoms, make_atoms_near};
+pub use segmenter::Segmenter;
+pub use store::{Backend, Store};
+pub use tokenizer::{Classic, Lines, Normalization, Prose, Tokenizer, Unicode};
+pub use workspace::{LockMode, Workspace};
 
-pub fn step(input: String) {
-    search::search(
-        fs::read_to_string(input).unwrap(),
-        "config.yaml",
-        "repo.yaml",
-    );
+pub fn step(input: String, workspace: &Workspace) -> Result<()> {
+    ingest(fs::read_to_string(input)?, workspace)
+}
+
+pub fn ingest(text: String, workspace: &Workspace) -> Result<()> {
+    search::search(text, workspace)
+}
+
+pub fn query(query: &Query, workspace: &Workspace) -> Result<Vec<LiteralOrtho>> {
+    Engine::open_existing(workspace.clone(), LockMode::Wait)?.query(query)
+}
+
+pub fn stats(workspace: &Workspace) -> Result<RepoStats> {
+    Engine::open_existing(workspace.clone(), LockMode::Wait)?.stats()
+}
+
+pub fn export(workspace: &Workspace, format: Format, target: impl std::io::Write) -> Result<()> {
+    Engine::open_existing(workspace.clone(), LockMode::Wait)?.export(format, target)
+}
+
+pub fn tokenizer(name: &str, abbreviations: Option<Vec<String>>) -> Result<Box<dyn Tokenizer>> {
+    tokenizer::named(name, abbreviations)
+}
+
+pub fn convert(workspace: &Workspace, force: bool) -> Result<()> {
+    Engine::convert(workspace.clone(), force)
+}
+
+pub fn compact(workspace: &Workspace) -> Result<()> {
+    Engine::open_existing(workspace.clone(), LockMode::Wait)?.compact()
+}
+
+pub fn reset(workspace: &Workspace) -> Result<()> {
+    if !workspace.exists() {
+        return Ok(());
+    }
+    let _lock = workspace.lock(LockMode::Wait)?;
+    Ok(workspace.reset()?)
 }
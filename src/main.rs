@@ -1,9 +1,21 @@
-use model_fold::step;
+mod cli;
+
 use std::env;
+use std::process;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let filename = &args[1];
-
-    step(filename.to_string());
+    let code = match cli::parse(env::args().skip(1)) {
+        Ok(invocation) => match cli::run(invocation) {
+            Ok(()) => 0,
+            Err(message) => {
+                eprintln!("error: {}", message);
+                1
+            }
+        },
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, cli::USAGE);
+            2
+        }
+    };
+    process::exit(code);
 }
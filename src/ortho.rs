@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 use std::hash::Hash;
 
+use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use string_interner::StringInterner;
 use string_interner::Symbol;
@@ -33,26 +34,82 @@ impl Ortho {
         Ortho { nodes }
     }
 
-    pub fn unintern(&self, interner: &StringInterner) -> LiteralOrtho {
-        LiteralOrtho {
+    pub fn from_grid(shape: &[usize], cells: &[usize]) -> Option<Ortho> {
+        // renderers and folds assume at least two axes
+        if shape.len() < 2
+            || shape.iter().any(|length| *length < 2)
+            || Self::volume(shape.iter()) != Some(cells.len())
+        {
+            return None;
+        }
+
+        let mut strides = vec![1; shape.len()];
+        for i in (0..shape.len() - 1).rev() {
+            strides[i] = strides[i + 1] * shape[i + 1];
+        }
+        let axes: Vec<usize> = strides.iter().map(|stride| cells[*stride]).collect();
+
+        let mut locations = BTreeMap::default();
+        for (index, name) in cells.iter().enumerate() {
+            let mut location = MultiSet::new();
+            for (k, axis) in axes.iter().enumerate() {
+                for _ in 0..(index / strides[k]) % shape[k] {
+                    location.insert(*axis);
+                }
+            }
+            if locations.insert(location, *name).is_some() {
+                return None;
+            }
+        }
+        Self::from_locations(locations)
+    }
+
+    pub fn from_locations(cells: BTreeMap<MultiSet, usize>) -> Option<Ortho> {
+        let axes: Vec<usize> = cells
+            .keys()
+            .filter(|location| location.total() == 1)
+            .flat_map(|location| location.items().cloned())
+            .collect();
+        if !cells.contains_key(&MultiSet::new()) || axes.len() < 2 {
+            return None;
+        }
+        if axes
+            .iter()
+            .any(|axis| cells.get(&MultiSet::new().add(*axis)) != Some(axis))
+        {
+            return None;
+        }
+
+        let mut dims: BTreeMap<usize, usize> = axes.iter().map(|axis| (*axis, 2)).collect();
+        for location in cells.keys() {
+            for axis in location.items() {
+                let length = dims.get_mut(axis)?;
+                *length = (*length).max(location.count(*axis) + 1);
+            }
+        }
+        if Self::volume(dims.values()) != Some(cells.len()) {
+            return None;
+        }
+
+        Some(Self::from_cells(cells.into_iter().collect()))
+    }
+
+    fn volume<'a>(mut lengths: impl Iterator<Item = &'a usize>) -> Option<usize> {
+        lengths.try_fold(1usize, |volume, length| volume.checked_mul(*length))
+    }
+
+    pub fn unintern(&self, interner: &StringInterner) -> Result<LiteralOrtho> {
+        Ok(LiteralOrtho {
             nodes: self
                 .nodes
                 .iter()
                 .map(|m| {
                     m.iter()
-                        .map(|(k, v)| {
-                            (
-                                k.unintern(interner),
-                                interner
-                                    .resolve(Symbol::try_from_usize(*v).unwrap())
-                                    .unwrap()
-                                    .to_string(),
-                            )
-                        })
+                        .map(|(k, v)| Ok((k.unintern(interner)?, resolve(interner, *v)?)))
                         .collect()
                 })
-                .collect(),
-        }
+                .collect::<Result<_>>()?,
+        })
     }
 
     pub fn size(&self) -> MultiSet {
@@ -67,11 +124,89 @@ impl Ortho {
         *name
     }
 
-    pub fn hop(&self) -> std::collections::btree_map::Values<MultiSet, usize> {
+    pub fn hop(&self) -> std::collections::btree_map::Values<'_, MultiSet, usize> {
         let mut nodes = self.nodes.iter();
         nodes.next();
         nodes.next().unwrap().values()
     }
+
+    pub fn axes(&self) -> Vec<usize> {
+        self.hop().cloned().collect()
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        let mut shape: Vec<usize> = self.dims().values().cloned().collect();
+        shape.sort_unstable();
+        shape
+    }
+
+    pub fn dims(&self) -> BTreeMap<usize, usize> {
+        let mut mapping = self.nodes.last().unwrap().iter();
+        let (location, _name) = mapping.next().unwrap();
+        self.axes()
+            .into_iter()
+            .map(|axis| (axis, location.count(axis) + 1))
+            .collect()
+    }
+
+    pub fn get(&self, location: &MultiSet) -> Option<usize> {
+        self.nodes.get(location.total())?.get(location).cloned()
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (&MultiSet, &usize)> {
+        self.nodes.iter().flat_map(|m| m.iter())
+    }
+
+    pub fn contains(&self, word: usize) -> bool {
+        self.find(word).is_some()
+    }
+
+    pub fn find(&self, word: usize) -> Option<&MultiSet> {
+        self.cells()
+            .find(|(_location, name)| **name == word)
+            .map(|(location, _name)| location)
+    }
+
+    pub fn footprint(&self) -> usize {
+        std::mem::size_of::<Ortho>()
+            + self.nodes.len() * std::mem::size_of::<BTreeMap<MultiSet, usize>>()
+            + self
+                .cells()
+                .map(|(location, _name)| location.footprint() + std::mem::size_of::<usize>())
+                .sum::<usize>()
+    }
+
+    pub fn zip_up(&self, other: &Ortho, mapping: &BTreeMap<usize, usize>) -> Option<Ortho> {
+        let shift = other.origin();
+        let mut cells: Vec<(MultiSet, usize)> =
+            self.cells().map(|(k, v)| (k.clone(), *v)).collect();
+        for (location, _name) in self.cells() {
+            let word = other.get(&location.rename(mapping))?;
+            cells.push((location.add(shift), word));
+        }
+        Some(Self::from_cells(cells))
+    }
+
+    pub fn extend(&self, cells: &BTreeMap<MultiSet, usize>) -> Ortho {
+        Self::from_cells(
+            self.cells()
+                .chain(cells.iter())
+                .map(|(k, v)| (k.clone(), *v))
+                .collect(),
+        )
+    }
+
+    fn from_cells(cells: Vec<(MultiSet, usize)>) -> Ortho {
+        let mut nodes: Vec<BTreeMap<MultiSet, usize>> = vec![];
+        for (location, name) in cells {
+            let distance = location.total();
+            if nodes.len() <= distance {
+                nodes.resize_with(distance + 1, BTreeMap::default);
+            }
+            nodes[distance].insert(location, name);
+        }
+        Ortho { nodes }
+    }
 }
 #[derive(PartialEq, Eq, Hash, Debug, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct LiteralOrtho {
@@ -79,18 +214,15 @@ pub struct LiteralOrtho {
 }
 
 impl LiteralOrtho {
-    pub fn intern(&self, interner: &StringInterner) -> Ortho {
-        Ortho {
-            nodes: self
-                .nodes
-                .iter()
-                .map(|m| {
-                    m.iter()
-                        .map(|(k, v)| (k.intern(interner), interner.get(v).unwrap().to_usize()))
-                        .collect()
-                })
-                .collect(),
+    pub fn intern(&self, interner: &StringInterner) -> Result<Ortho> {
+        let mut cells = BTreeMap::default();
+        for m in self.nodes.iter() {
+            for (k, v) in m.iter() {
+                cells.insert(k.intern(interner)?, lookup(interner, v)?);
+            }
         }
+        Ortho::from_locations(cells)
+            .ok_or_else(|| Error::InconsistentRepo("malformed ortho".to_string()))
     }
 }
 
@@ -100,14 +232,14 @@ pub struct LiteralMultiSet {
 }
 
 impl LiteralMultiSet {
-    pub fn intern(&self, interner: &StringInterner) -> MultiSet {
-        MultiSet {
+    pub fn intern(&self, interner: &StringInterner) -> Result<MultiSet> {
+        Ok(MultiSet {
             set: self
                 .set
                 .iter()
-                .map(|(k, v)| (interner.get(k.clone()).unwrap().to_usize(), *v))
-                .collect(),
-        }
+                .map(|(k, v)| Ok((lookup(interner, k)?, *v)))
+                .collect::<Result<_>>()?,
+        })
     }
 }
 
@@ -117,21 +249,13 @@ pub struct MultiSet {
 }
 
 impl MultiSet {
-    pub fn unintern(&self, interner: &StringInterner) -> LiteralMultiSet {
+    pub fn unintern(&self, interner: &StringInterner) -> Result<LiteralMultiSet> {
         let res = self
             .set
             .iter()
-            .map(|(k, v)| {
-                (
-                    interner
-                        .resolve(Symbol::try_from_usize(*k).unwrap())
-                        .unwrap()
-                        .to_string(),
-                    *v,
-                )
-            })
-            .collect();
-        LiteralMultiSet { set: res }
+            .map(|(k, v)| Ok((resolve(interner, *k)?, *v)))
+            .collect::<Result<_>>()?;
+        Ok(LiteralMultiSet { set: res })
     }
 
     pub fn new() -> MultiSet {
@@ -149,8 +273,63 @@ impl MultiSet {
     }
 
     pub fn insert(&mut self, item: usize) {
-        let count = self.set.entry(item).or_insert(0);
-        *count += 1;
+        self.insert_many(item, 1);
+    }
+
+    pub fn insert_many(&mut self, item: usize, times: usize) {
+        if times > 0 {
+            *self.set.entry(item).or_insert(0) += times;
+        }
+    }
+
+    pub fn from_shape(shape: &[usize]) -> MultiSet {
+        let mut set = MultiSet::new();
+        for length in shape {
+            set.insert(length.saturating_sub(1));
+        }
+        set
+    }
+
+    pub fn footprint(&self) -> usize {
+        std::mem::size_of::<MultiSet>() + self.set.len() * 2 * std::mem::size_of::<usize>()
+    }
+
+    pub fn count(&self, item: usize) -> usize {
+        self.set.get(&item).cloned().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.set.values().sum()
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &usize> {
+        self.set.keys()
+    }
+
+    pub fn add(&self, item: usize) -> MultiSet {
+        let mut set = self.clone();
+        set.insert(item);
+        set
+    }
+
+    pub fn remove(&self, item: usize) -> MultiSet {
+        let mut set = self.clone();
+        if let Some(count) = set.set.get_mut(&item) {
+            *count -= 1;
+            if *count == 0 {
+                set.set.remove(&item);
+            }
+        }
+        set
+    }
+
+    pub fn rename(&self, mapping: &BTreeMap<usize, usize>) -> MultiSet {
+        let mut set = MultiSet::new();
+        for (key, value) in self.set.iter() {
+            let name = mapping.get(key).cloned().unwrap_or(*key);
+            *set.set.entry(name).or_insert(0) += value;
+        }
+        set
     }
 }
 
@@ -160,6 +339,20 @@ impl Default for MultiSet {
     }
 }
 
+pub fn resolve(interner: &StringInterner, symbol: usize) -> Result<String> {
+    Symbol::try_from_usize(symbol)
+        .and_then(|symbol| interner.resolve(symbol))
+        .map(|word| word.to_string())
+        .ok_or_else(|| Error::UnknownSymbol(format!("#{}", symbol)))
+}
+
+pub fn lookup(interner: &StringInterner, word: &str) -> Result<usize> {
+    interner
+        .get(word)
+        .map(|symbol| symbol.to_usize())
+        .ok_or_else(|| Error::UnknownSymbol(word.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -174,6 +367,66 @@ mod tests {
         assert_eq!(ortho.size(), ortho2.size());
     }
 
+    #[test]
+    fn it_can_be_built_from_a_grid() {
+        assert_eq!(
+            Ortho::from_grid(&[2, 2], &[1, 2, 3, 4]),
+            Some(Ortho::new(1, 2, 3, 4))
+        );
+        assert_eq!(
+            Ortho::from_grid(&[2, 2], &[1, 2, 3, 4]),
+            Ortho::from_grid(&[2, 2], &[1, 3, 2, 4])
+        );
+    }
+
+    #[test]
+    fn it_compares_equal_across_rotation_in_any_shape() {
+        let wide = Ortho::from_grid(&[2, 3], &[1, 2, 5, 3, 4, 6]).unwrap();
+        let tall = Ortho::from_grid(&[3, 2], &[1, 3, 2, 4, 5, 6]).unwrap();
+        assert_eq!(wide, tall);
+        assert_eq!(wide.shape(), vec![2, 3]);
+
+        let cube = Ortho::from_grid(&[2, 2, 2], &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let rotated = Ortho::from_grid(&[2, 2, 2], &[1, 5, 3, 7, 2, 6, 4, 8]).unwrap();
+        assert_eq!(cube, rotated);
+        assert_eq!(cube.shape(), vec![2, 2, 2]);
+        assert_eq!(cube.origin(), 1);
+    }
+
+    #[test]
+    fn it_rejects_inconsistent_grids() {
+        assert_eq!(Ortho::from_grid(&[2, 2], &[1, 2, 3]), None);
+        assert_eq!(Ortho::from_grid(&[2, 1], &[1, 2]), None);
+        assert_eq!(Ortho::from_grid(&[], &[]), None);
+        assert_eq!(Ortho::from_grid(&[3], &[1, 2, 3]), None);
+        assert_eq!(Ortho::from_grid(&[usize::MAX, 2, 2], &[1, 2, 3, 4]), None);
+        assert_eq!(Ortho::from_grid(&[2, 2], &[1, 2, 2, 4]), None);
+    }
+
+    #[test]
+    fn it_rejects_inconsistent_locations() {
+        let mut cells = BTreeMap::default();
+        cells.insert(MultiSet::new(), 1);
+        cells.insert(MultiSet::new().add(2), 2);
+        assert_eq!(Ortho::from_locations(cells.clone()), None);
+
+        cells.insert(MultiSet::new().add(2).add(2), 3);
+        assert_eq!(Ortho::from_locations(cells.clone()), None);
+        cells.remove(&MultiSet::new().add(2).add(2));
+
+        cells.insert(MultiSet::new().add(3), 3);
+        assert_eq!(Ortho::from_locations(cells.clone()), None);
+
+        cells.insert(MultiSet::new().add(2).add(3), 4);
+        assert_eq!(
+            Ortho::from_locations(cells.clone()),
+            Some(Ortho::new(1, 2, 3, 4))
+        );
+
+        cells.insert(MultiSet::new().add(9), 5);
+        assert_eq!(Ortho::from_locations(cells), None);
+    }
+
     #[test]
     fn it_exposes_origin() {
         assert_eq!(Ortho::new(1, 2, 3, 4).origin(), 1);
@@ -183,7 +436,7 @@ mod tests {
     fn it_exposes_hop() {
         let ortho = Ortho::new(1, 2, 3, 4);
         let actual: Vec<&usize> = ortho.hop().collect();
-        let expected = vec![&(2 as usize), &(3 as usize)];
+        let expected = vec![&2_usize, &3_usize];
 
         assert_eq!(actual, expected);
     }
@@ -196,16 +449,87 @@ mod tests {
         assert_eq!(Ortho::new(10, 20, 30, 40).size(), expected);
     }
 
+    #[test]
+    fn it_exposes_cells_by_location() {
+        let ortho = Ortho::new(1, 2, 3, 4);
+        let mut far = MultiSet::new();
+        far.insert(2);
+        far.insert(3);
+
+        assert_eq!(ortho.get(&MultiSet::new()), Some(1));
+        assert_eq!(ortho.get(&far), Some(4));
+        assert_eq!(ortho.cells().count(), 4);
+        assert_eq!(ortho.dims().values().collect::<Vec<_>>(), vec![&2, &2]);
+    }
+
+    #[test]
+    fn it_zips_up_into_a_new_dimension() {
+        let bottom = Ortho::new(1, 2, 3, 4);
+        let top = Ortho::new(5, 6, 7, 8);
+        let mapping = vec![(2, 6), (3, 7)].into_iter().collect();
+        let cube = bottom.zip_up(&top, &mapping).unwrap();
+
+        let mut expected = MultiSet::new();
+        expected.insert(1);
+        expected.insert(1);
+        expected.insert(1);
+
+        assert_eq!(cube.size(), expected);
+        assert_eq!(cube.origin(), 1);
+        assert_eq!(cube.axes(), vec![2, 3, 5]);
+        assert_eq!(cube.get(&MultiSet::new().add(2).add(3).add(5)), Some(8));
+    }
+
+    #[test]
+    fn it_knows_its_words() {
+        let mut cells = BTreeMap::default();
+        cells.insert(MultiSet::new().add(2).add(2), 4);
+        cells.insert(MultiSet::new().add(2).add(2).add(3), 5);
+
+        assert!(Ortho::new(1, 2, 3, 4).contains(4));
+        assert!(!Ortho::new(1, 2, 3, 4).contains(5));
+        assert!(Ortho::new(1, 2, 3, 4).extend(&cells).contains(5));
+    }
+
+    #[test]
+    fn it_extends_along_an_axis() {
+        let ortho = Ortho::new(1, 2, 3, 4);
+        let mut cells = BTreeMap::default();
+        cells.insert(MultiSet::new().add(2).add(2), 5);
+        cells.insert(MultiSet::new().add(2).add(2).add(3), 6);
+        let extended = ortho.extend(&cells);
+
+        let mut expected = MultiSet::new();
+        expected.insert(1);
+        expected.insert(2);
+
+        assert_eq!(extended.size(), expected);
+        assert_eq!(extended.origin(), 1);
+        assert_eq!(extended.axes(), vec![2, 3]);
+        assert_eq!(extended.dims().get(&2), Some(&3));
+    }
+
+    #[test]
+    fn it_has_size_matching_its_shape() {
+        let ortho = Ortho::from_grid(&[2, 3], &[1, 2, 5, 3, 4, 6]).unwrap();
+        assert_eq!(ortho.size(), MultiSet::from_shape(&[3, 2]));
+        assert_eq!(Ortho::new(1, 2, 3, 4).size(), MultiSet::from_shape(&[2, 2]));
+    }
+
     #[test]
     fn it_has_multisets_that_can_be_interned_or_uninterned() {
         let mut expected = MultiSet::new();
         let mut interner = StringInterner::default();
-        expected.insert(interner.get_or_intern("a".to_string()).to_usize());
-        expected.insert(interner.get_or_intern("a".to_string()).to_usize());
+        expected.insert(interner.get_or_intern("a").to_usize());
+        expected.insert(interner.get_or_intern("a").to_usize());
 
         assert_eq!(
             expected,
-            expected.unintern(&mut interner).intern(&mut interner)
+            expected
+                .unintern(&interner)
+                .unwrap()
+                .intern(&interner)
+                .unwrap()
         )
     }
 
@@ -219,17 +543,24 @@ mod tests {
             interner.get_or_intern("d").to_usize(),
         );
 
-        assert_eq!(ortho, ortho.unintern(&mut interner).intern(&mut interner))
+        assert_eq!(
+            ortho,
+            ortho
+                .unintern(&interner)
+                .unwrap()
+                .intern(&interner)
+                .unwrap()
+        )
     }
 
     #[test]
     fn multiset_serializes() {
         let mut expected = MultiSet::new();
         let mut interner = StringInterner::default();
-        expected.insert(interner.get_or_intern("a".to_string()).to_usize());
-        expected.insert(interner.get_or_intern("a".to_string()).to_usize());
+        expected.insert(interner.get_or_intern("a").to_usize());
+        expected.insert(interner.get_or_intern("a").to_usize());
 
-        let uninterned = expected.unintern(&mut interner);
+        let uninterned = expected.unintern(&interner).unwrap();
         let serialized = serde_yaml::to_string(&uninterned).unwrap();
         let deserialized: LiteralMultiSet = serde_yaml::from_str(&serialized).unwrap();
         assert_eq!(uninterned, deserialized);
@@ -246,10 +577,31 @@ mod tests {
             interner.get_or_intern("d").to_usize(),
         );
 
-        let literal_ortho = ortho.unintern(&mut interner);
+        let literal_ortho = ortho.unintern(&interner).unwrap();
         let serialized = serde_yaml::to_string(&literal_ortho).unwrap();
         let deserialized: LiteralOrtho = serde_yaml::from_str(&serialized).unwrap();
 
         assert_eq!(literal_ortho, deserialized);
     }
+
+    #[test]
+    fn it_reports_unknown_symbols() {
+        let mut interner = StringInterner::default();
+        let a = interner.get_or_intern("a").to_usize();
+        let ortho = Ortho::new(a, a + 1, a + 2, a + 3);
+
+        assert!(matches!(
+            ortho.unintern(&interner),
+            Err(Error::UnknownSymbol(_))
+        ));
+
+        let b = interner.get_or_intern("b").to_usize();
+        let c = interner.get_or_intern("c").to_usize();
+        let d = interner.get_or_intern("d").to_usize();
+        let literal = Ortho::new(a, b, c, d).unintern(&interner).unwrap();
+        assert!(matches!(
+            literal.intern(&StringInterner::default()),
+            Err(Error::UnknownSymbol(_))
+        ));
+    }
 }
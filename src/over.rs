@@ -0,0 +1,152 @@
+use crate::config::Support;
+use crate::error::Result;
+use crate::ortho::MultiSet;
+use crate::repo::RepoStore;
+use crate::{Config, Ortho};
+use nohash_hasher::IntSet;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+pub fn create(
+    config: &Config,
+    support: Support,
+    repo: &impl RepoStore,
+    ortho: &Ortho,
+) -> Result<BTreeSet<Ortho>> {
+    let mut results = BTreeSet::default();
+    // a -> b -> e
+    // |    |    |
+    // c -> d -> f
+    // new cells never repeat a word already in the ortho
+
+    for (axis, length) in ortho.dims() {
+        let mut pending: Vec<MultiSet> = ortho
+            .cells()
+            .filter(|(location, _name)| location.count(axis) == length - 1)
+            .map(|(location, _name)| location.add(axis))
+            .collect();
+        pending.sort_by_key(|location| location.total());
+
+        fill(
+            config,
+            support,
+            ortho,
+            &pending,
+            &mut BTreeMap::default(),
+            &mut results,
+        );
+    }
+    repo.set_subract(results)
+}
+
+fn fill(
+    config: &Config,
+    support: Support,
+    ortho: &Ortho,
+    pending: &[MultiSet],
+    filled: &mut BTreeMap<MultiSet, usize>,
+    results: &mut BTreeSet<Ortho>,
+) {
+    match pending.split_first() {
+        None => {
+            results.insert(ortho.extend(filled));
+        }
+        Some((location, rest)) => {
+            for word in candidates(config, support, ortho, filled, location) {
+                filled.insert(location.clone(), word);
+                fill(config, support, ortho, rest, filled, results);
+                filled.remove(location);
+            }
+        }
+    }
+}
+
+fn candidates(
+    config: &Config,
+    support: Support,
+    ortho: &Ortho,
+    filled: &BTreeMap<MultiSet, usize>,
+    location: &MultiSet,
+) -> IntSet<usize> {
+    let mut result: Option<IntSet<usize>> = None;
+    for axis in location.items() {
+        let previous = location.remove(*axis);
+        let word = match filled.get(&previous) {
+            Some(word) => *word,
+            None => ortho.get(&previous).unwrap(),
+        };
+        let potential = match config.project_forward_supported(word, support) {
+            Some(potential) => potential,
+            None => return IntSet::default(),
+        };
+        result = Some(match result {
+            None => potential.into_owned(),
+            Some(current) => current.intersection(&potential).cloned().collect(),
+        });
+    }
+    let mut result = result.unwrap_or_default();
+    result.retain(|word| !ortho.contains(*word) && !filled.values().any(|name| name == word));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::Repo;
+    use string_interner::{StringInterner, Symbol};
+
+    fn square(interner: &StringInterner, words: [&str; 4]) -> Ortho {
+        let [a, b, c, d] = words.map(|word| interner.get(word).unwrap().to_usize());
+        Ortho::new(a, b, c, d)
+    }
+
+    #[test]
+    fn it_extends_a_square_into_a_rectangle() {
+        let (config, interner) = Config::from_sentences("a b e. c d f. a c. b d. e f.".to_string());
+        let repo = Repo::new();
+        let res = create(
+            &config,
+            Support::default(),
+            &repo,
+            &square(&interner, ["a", "b", "c", "d"]),
+        )
+        .unwrap();
+
+        assert_eq!(res.len(), 1);
+        let rectangle = res.iter().next().unwrap();
+        let mut expected = MultiSet::new();
+        expected.insert(1);
+        expected.insert(2);
+        assert_eq!(rectangle.size(), expected);
+        assert_eq!(rectangle.cells().count(), 6);
+    }
+
+    #[test]
+    fn it_requires_every_column_to_be_a_chain() {
+        let (config, interner) = Config::from_sentences("a b e. c d f. a c. b d.".to_string());
+        let repo = Repo::new();
+        let res = create(
+            &config,
+            Support::default(),
+            &repo,
+            &square(&interner, ["a", "b", "c", "d"]),
+        )
+        .unwrap();
+
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn it_will_not_return_the_same_thing_twice() {
+        let (config, interner) = Config::from_sentences("a b e. c d f. a c. b d. e f.".to_string());
+        let mut repo = Repo::new();
+        let ortho = square(&interner, ["a", "b", "c", "d"]);
+        for found in create(&config, Support::default(), &repo, &ortho).unwrap() {
+            repo.add(found).unwrap();
+        }
+
+        assert!(create(&config, Support::default(), &repo, &ortho)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::error::Result;
+use crate::ortho::MultiSet;
+use crate::render;
+use crate::repo::RepoStore;
+use crate::Ortho;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use string_interner::StringInterner;
+use string_interner::Symbol;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub origin: Option<String>,
+    pub hop: Option<String>,
+    pub shape: Option<Vec<usize>>,
+    pub contains: Vec<String>,
+    pub at: Option<(String, Vec<usize>)>,
+}
+
+impl Query {
+    pub fn run(&self, repo: &impl RepoStore, interner: &StringInterner) -> Result<BTreeSet<Ortho>> {
+        let origin = match Self::lookup(&self.origin, interner) {
+            Some(origin) => origin,
+            None => return Ok(BTreeSet::default()),
+        };
+        let hop = match Self::lookup(&self.hop, interner) {
+            Some(hop) => hop,
+            None => return Ok(BTreeSet::default()),
+        };
+        let shape = self.shape.clone().map(|mut shape| {
+            shape.sort_unstable();
+            shape
+        });
+        let contains = match self
+            .contains
+            .iter()
+            .map(|word| interner.get(word).map(|symbol| symbol.to_usize()))
+            .collect::<Option<Vec<usize>>>()
+        {
+            Some(contains) => contains,
+            None => return Ok(BTreeSet::default()),
+        };
+        let at = match &self.at {
+            None => None,
+            Some((word, coordinates)) => match interner.get(word) {
+                Some(symbol) => Some((symbol.to_usize(), coordinates)),
+                None => return Ok(BTreeSet::default()),
+            },
+        };
+
+        let bucket = match (&shape, origin, hop) {
+            (Some(shape), Some(origin), _) => {
+                Some(repo.find_by_size_and_origin(MultiSet::from_shape(shape), origin)?)
+            }
+            (Some(shape), None, Some(hop)) => {
+                Some(repo.find_by_size_and_hop(MultiSet::from_shape(shape), hop)?)
+            }
+            _ => None,
+        };
+        let found;
+        let candidates: Box<dyn Iterator<Item = Result<Cow<'_, Ortho>>>> = match (&bucket, &at) {
+            (Some(bucket), _) => Box::new(
+                bucket
+                    .iter()
+                    .flat_map(|bucket| bucket.iter())
+                    .map(|o| Ok(Cow::Borrowed(o))),
+            ),
+            (None, Some((word, coordinates))) => {
+                found = repo.find_by_word_at(*word, coordinates, interner)?;
+                Box::new(found.iter().map(|o| Ok(Cow::Borrowed(o))))
+            }
+            (None, None) if !contains.is_empty() => {
+                found = repo.find_by_words(&contains)?;
+                Box::new(found.iter().map(|o| Ok(Cow::Borrowed(o))))
+            }
+            (None, None) => repo.orthos(),
+        };
+
+        let mut results = BTreeSet::default();
+        for o in candidates {
+            let o = o?;
+            let matches = origin.is_none_or(|origin| o.origin() == origin)
+                && contains.iter().all(|word| o.contains(*word))
+                && hop.is_none_or(|hop| o.hop().any(|h| *h == hop))
+                && shape.as_ref().is_none_or(|shape| o.shape() == *shape);
+            let matches = matches
+                && match &at {
+                    Some((word, coordinates)) => render::is_at(&o, *word, coordinates, interner)?,
+                    None => true,
+                };
+            if matches {
+                results.insert(o.into_owned());
+            }
+        }
+        Ok(results)
+    }
+
+    fn lookup(word: &Option<String>, interner: &StringInterner) -> Option<Option<usize>> {
+        match word {
+            None => Some(None),
+            Some(word) => interner.get(word).map(|symbol| Some(symbol.to_usize())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::Repo;
+
+    fn repo(interner: &mut StringInterner) -> Repo {
+        let mut repo = Repo::new();
+        let [a, b, c, d, e, f] =
+            ["a", "b", "c", "d", "e", "f"].map(|word| interner.get_or_intern(word).to_usize());
+        repo.add(Ortho::new(a, b, c, d)).unwrap();
+        repo.add(Ortho::new(e, b, f, d)).unwrap();
+        repo.add(Ortho::from_grid(&[2, 3], &[a, b, e, c, d, f]).unwrap())
+            .unwrap();
+        repo
+    }
+
+    #[test]
+    fn it_finds_by_origin() {
+        let mut interner = StringInterner::default();
+        let repo = repo(&mut interner);
+        let query = Query {
+            origin: Some("a".to_string()),
+            ..Query::default()
+        };
+        assert_eq!(query.run(&repo, &interner).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn it_finds_by_hop_and_shape() {
+        let mut interner = StringInterner::default();
+        let repo = repo(&mut interner);
+        let query = Query {
+            hop: Some("b".to_string()),
+            shape: Some(vec![2, 2]),
+            ..Query::default()
+        };
+        assert_eq!(query.run(&repo, &interner).unwrap().len(), 2);
+
+        let query = Query {
+            origin: Some("a".to_string()),
+            shape: Some(vec![3, 2]),
+            ..Query::default()
+        };
+        assert_eq!(query.run(&repo, &interner).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn it_finds_by_contents() {
+        let mut interner = StringInterner::default();
+        let repo = repo(&mut interner);
+        let query = Query {
+            contains: vec!["d".to_string()],
+            ..Query::default()
+        };
+        assert_eq!(query.run(&repo, &interner).unwrap().len(), 3);
+
+        let query = Query {
+            contains: vec!["a".to_string(), "f".to_string()],
+            ..Query::default()
+        };
+        assert_eq!(query.run(&repo, &interner).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn it_finds_by_position() {
+        let mut interner = StringInterner::default();
+        let repo = repo(&mut interner);
+        let far_corner = |word: &str| Query {
+            at: Some((word.to_string(), vec![1, 1])),
+            ..Query::default()
+        };
+        assert_eq!(far_corner("d").run(&repo, &interner).unwrap().len(), 3);
+        assert_eq!(far_corner("f").run(&repo, &interner).unwrap().len(), 0);
+
+        let query = Query {
+            shape: Some(vec![2, 2]),
+            ..far_corner("d")
+        };
+        assert_eq!(query.run(&repo, &interner).unwrap().len(), 2);
+
+        let query = Query {
+            at: Some(("f".to_string(), vec![1, 2])),
+            shape: Some(vec![2, 3]),
+            ..Query::default()
+        };
+        assert_eq!(query.run(&repo, &interner).unwrap().len(), 1);
+
+        let query = Query {
+            at: Some(("a".to_string(), vec![0])),
+            contains: vec!["e".to_string()],
+            ..Query::default()
+        };
+        assert_eq!(query.run(&repo, &interner).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn it_tells_axes_apart() {
+        let mut interner = StringInterner::default();
+        let repo = repo(&mut interner);
+        let at = |word: &str, coordinates: Vec<usize>| {
+            Query {
+                at: Some((word.to_string(), coordinates)),
+                ..Query::default()
+            }
+            .run(&repo, &interner)
+            .unwrap()
+            .len()
+        };
+        // row then column, as show draws them
+        assert_eq!(at("e", vec![0, 2]), 1);
+        assert_eq!(at("e", vec![2, 0]), 0);
+        assert_eq!(at("b", vec![0, 1]), 3);
+        assert_eq!(at("b", vec![1, 0]), 0);
+        assert_eq!(at("c", vec![1]), 2);
+        assert_eq!(at("c", vec![0, 1]), 0);
+    }
+
+    #[test]
+    fn it_finds_nothing_for_unknown_words() {
+        let mut interner = StringInterner::default();
+        let repo = repo(&mut interner);
+        let query = Query {
+            origin: Some("zebra".to_string()),
+            ..Query::default()
+        };
+        assert!(query.run(&repo, &interner).unwrap().is_empty());
+
+        let query = Query {
+            contains: vec!["a".to_string(), "zebra".to_string()],
+            ..Query::default()
+        };
+        assert!(query.run(&repo, &interner).unwrap().is_empty());
+    }
+}
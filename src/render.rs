@@ -0,0 +1,171 @@
+use crate::error::Result;
+use crate::ortho::{resolve, MultiSet, Ortho};
+use string_interner::StringInterner;
+
+// longest axis first, ties broken by the word naming the axis, so the order
+// does not depend on symbol ids
+pub fn axes(ortho: &Ortho, interner: &StringInterner) -> Result<Vec<(String, usize, usize)>> {
+    let mut axes = ortho
+        .dims()
+        .into_iter()
+        .map(|(axis, length)| Ok((resolve(interner, axis)?, axis, length)))
+        .collect::<Result<Vec<_>>>()?;
+    axes.sort_by(|(a_word, _, a_length), (b_word, _, b_length)| {
+        b_length.cmp(a_length).then_with(|| a_word.cmp(b_word))
+    });
+    Ok(axes)
+}
+
+// shortest axis first, the order sizes are written in, so a grid location
+// reads as row then column the way render draws it
+pub fn coordinates(
+    ortho: &Ortho,
+    location: &MultiSet,
+    interner: &StringInterner,
+) -> Result<Vec<usize>> {
+    Ok(axes(ortho, interner)?
+        .iter()
+        .rev()
+        .map(|(_word, axis, _length)| location.count(*axis))
+        .collect())
+}
+
+// coordinates left off the end are zero
+pub fn is_at(
+    ortho: &Ortho,
+    word: usize,
+    expected: &[usize],
+    interner: &StringInterner,
+) -> Result<bool> {
+    let location = match ortho.find(word) {
+        Some(location) => location,
+        None => return Ok(false),
+    };
+    let found = coordinates(ortho, location, interner)?;
+    Ok((0..found.len().max(expected.len()))
+        .all(|i| found.get(i).cloned().unwrap_or(0) == expected.get(i).cloned().unwrap_or(0)))
+}
+
+// columns run along the longest axis and rows along the next, so every row
+// reads as a forward chain; further axes become labelled 2-d slices
+pub fn render(ortho: &Ortho, interner: &StringInterner) -> Result<String> {
+    let axes = axes(ortho, interner)?;
+    let (columns, rows, rest) = match axes.as_slice() {
+        [columns, rows, rest @ ..] => (columns, rows, rest),
+        _ => unreachable!("orthos have at least two axes"),
+    };
+
+    let mut slices = vec![vec![]];
+    for (_word, axis, length) in rest {
+        slices = slices
+            .into_iter()
+            .flat_map(|slice: Vec<(usize, usize)>| {
+                (0..*length).map(move |coordinate| {
+                    let mut slice = slice.clone();
+                    slice.push((*axis, coordinate));
+                    slice
+                })
+            })
+            .collect();
+    }
+
+    let mut grids = vec![];
+    for slice in &slices {
+        let mut grid = vec![];
+        for row in 0..rows.2 {
+            let mut line = vec![];
+            for column in 0..columns.2 {
+                let mut location = MultiSet::new();
+                for (axis, count) in [(columns.1, column), (rows.1, row)]
+                    .iter()
+                    .chain(slice.iter())
+                {
+                    for _ in 0..*count {
+                        location.insert(*axis);
+                    }
+                }
+                line.push(resolve(interner, ortho.get(&location).unwrap())?);
+            }
+            grid.push(line);
+        }
+        grids.push(grid);
+    }
+
+    let widths: Vec<usize> = (0..columns.2)
+        .map(|column| {
+            grids
+                .iter()
+                .flatten()
+                .map(|line| line[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut out = vec![];
+    for (slice, grid) in slices.iter().zip(grids) {
+        if !slice.is_empty() {
+            if !out.is_empty() {
+                out.push(String::new());
+            }
+            let label: Vec<String> = slice
+                .iter()
+                .zip(rest)
+                .map(|((_axis, coordinate), (word, _, _))| format!("{}={}", word, coordinate))
+                .collect();
+            out.push(format!("[{}]", label.join(" ")));
+        }
+        for line in grid {
+            let cells: Vec<String> = line
+                .iter()
+                .zip(&widths)
+                .map(|(word, width)| format!("{:width$}", word, width = width))
+                .collect();
+            out.push(cells.join(" ").trim_end().to_string());
+        }
+    }
+    Ok(out.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use string_interner::Symbol;
+
+    fn ids(interner: &mut StringInterner, words: &[&str]) -> Vec<usize> {
+        words
+            .iter()
+            .map(|word| interner.get_or_intern(word).to_usize())
+            .collect()
+    }
+
+    #[test]
+    fn it_renders_a_square() {
+        let mut interner = StringInterner::default();
+        let cells = ids(&mut interner, &["the", "cat", "big", "dog"]);
+        let ortho = Ortho::from_grid(&[2, 2], &cells).unwrap();
+
+        assert_eq!(render(&ortho, &interner).unwrap(), "the big\ncat dog");
+    }
+
+    #[test]
+    fn it_aligns_columns_along_the_longest_axis() {
+        let mut interner = StringInterner::default();
+        let cells = ids(&mut interner, &["a", "b", "eee", "cc", "d", "f"]);
+        let ortho = Ortho::from_grid(&[2, 3], &cells).unwrap();
+
+        assert_eq!(render(&ortho, &interner).unwrap(), "a  b eee\ncc d f");
+    }
+
+    #[test]
+    fn it_renders_higher_dimensions_as_slices() {
+        let mut interner = StringInterner::default();
+        let cells = ids(&mut interner, &["a", "b", "c", "d", "e", "f", "g", "h"]);
+        let ortho = Ortho::from_grid(&[2, 2, 2], &cells).unwrap();
+
+        assert_eq!(
+            render(&ortho, &interner).unwrap(),
+            "[e=0]\na b\nc d\n\n[e=1]\ne f\ng h"
+        );
+    }
+}
@@ -1,92 +1,316 @@
+use crate::error::{Error, Result};
 use crate::ortho::LiteralOrtho;
 use crate::ortho::MultiSet;
 use crate::ortho::Ortho;
+use crate::ortho::{lookup, resolve};
+use crate::render;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
+use std::fmt;
+use std::io::{Read, Write};
 use string_interner::StringInterner;
-use string_interner::Symbol;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Repo {
     origin: HashMap<(MultiSet, usize), BTreeSet<Ortho>>,
     hops: HashMap<(MultiSet, usize), BTreeSet<Ortho>>,
+    contents: HashMap<usize, BTreeSet<Ortho>>,
 }
 
-impl Repo {
-    pub fn set_subract(&self, from: BTreeSet<Ortho>) -> BTreeSet<Ortho> {
-        from.iter()
-            .filter(|&o| self.find_by_size_and_origin(o.size(), o.origin()).is_none())
-            .cloned()
-            .collect()
+pub trait RepoStore: Sync {
+    fn add(&mut self, ortho: Ortho) -> Result<()>;
+
+    fn contains(&self, ortho: &Ortho) -> Result<bool>;
+
+    fn find_by_size_and_origin(
+        &self,
+        size: MultiSet,
+        origin: usize,
+    ) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>>;
+
+    fn find_by_size_and_hop(
+        &self,
+        size: MultiSet,
+        hop: usize,
+    ) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>>;
+
+    fn find_by_word(&self, word: usize) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>>;
+
+    fn orthos(&self) -> Box<dyn Iterator<Item = Result<Cow<'_, Ortho>>> + '_>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn set_subract(&self, from: BTreeSet<Ortho>) -> Result<BTreeSet<Ortho>> {
+        let mut kept = BTreeSet::default();
+        for ortho in from {
+            if !self.contains(&ortho)? {
+                kept.insert(ortho);
+            }
+        }
+        Ok(kept)
+    }
+
+    fn find_by_word_at(
+        &self,
+        word: usize,
+        coordinates: &[usize],
+        interner: &StringInterner,
+    ) -> Result<BTreeSet<Ortho>> {
+        let mut found = BTreeSet::default();
+        for ortho in self
+            .find_by_word(word)?
+            .into_iter()
+            .flat_map(|b| b.into_owned())
+        {
+            if render::is_at(&ortho, word, coordinates, interner)? {
+                found.insert(ortho);
+            }
+        }
+        Ok(found)
+    }
+
+    fn find_by_words(&self, words: &[usize]) -> Result<BTreeSet<Ortho>> {
+        let mut smallest: Option<Cow<'_, BTreeSet<Ortho>>> = None;
+        for word in words {
+            let bucket = self.find_by_word(*word)?.unwrap_or_default();
+            if smallest.as_ref().is_none_or(|s| bucket.len() < s.len()) {
+                smallest = Some(bucket);
+            }
+        }
+        Ok(smallest
+            .into_iter()
+            .flat_map(|bucket| bucket.into_owned())
+            .filter(|o| words.iter().all(|word| o.contains(*word)))
+            .collect())
+    }
+}
+
+impl RepoStore for Repo {
+    fn add(&mut self, ortho: Ortho) -> Result<()> {
+        self.origin
+            .entry((ortho.size(), ortho.origin()))
+            .or_default()
+            .insert(ortho.clone());
+
+        for hop in ortho.hop() {
+            self.hops
+                .entry((ortho.size(), *hop))
+                .or_default()
+                .insert(ortho.clone());
+        }
+
+        for (_location, word) in ortho.cells() {
+            self.contents
+                .entry(*word)
+                .or_default()
+                .insert(ortho.clone());
+        }
+        Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn len(&self) -> usize {
-        self.origin.len()
+    fn contains(&self, ortho: &Ortho) -> Result<bool> {
+        Ok(self
+            .origin
+            .get(&(ortho.size(), ortho.origin()))
+            .is_some_and(|bucket| bucket.contains(ortho)))
     }
 
-    pub fn find_by_size_and_origin(
+    fn find_by_size_and_origin(
         &self,
         size: MultiSet,
         origin: usize,
-    ) -> Option<&BTreeSet<Ortho>> {
-        self.origin.get(&(size, origin))
+    ) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>> {
+        Ok(self.origin.get(&(size, origin)).map(Cow::Borrowed))
+    }
+
+    fn find_by_size_and_hop(
+        &self,
+        size: MultiSet,
+        hop: usize,
+    ) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>> {
+        Ok(self.hops.get(&(size, hop)).map(Cow::Borrowed))
+    }
+
+    fn find_by_word(&self, word: usize) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>> {
+        Ok(self.contents.get(&word).map(Cow::Borrowed))
+    }
+
+    fn orthos(&self) -> Box<dyn Iterator<Item = Result<Cow<'_, Ortho>>> + '_> {
+        Box::new(self.iter().map(|o| Ok(Cow::Borrowed(o))))
+    }
+
+    fn len(&self) -> usize {
+        self.origin.values().map(|bucket| bucket.len()).sum()
+    }
+}
+
+impl Repo {
+    pub fn stats(&self) -> RepoStats {
+        let mut shapes = BTreeMap::default();
+        for ortho in self.iter() {
+            *shapes.entry(ortho.shape()).or_insert(0) += 1;
+        }
+
+        let mut hop_fan_out = BTreeMap::default();
+        for bucket in self.hops.values() {
+            *hop_fan_out.entry(bucket.len()).or_insert(0) += 1;
+        }
+
+        RepoStats {
+            orthos: self.len(),
+            shapes,
+            origins: self
+                .origin
+                .keys()
+                .map(|(_size, origin)| origin)
+                .collect::<BTreeSet<_>>()
+                .len(),
+            hop_fan_out,
+            index_bytes: Self::footprint(&self.origin)
+                + Self::footprint(&self.hops)
+                + self
+                    .contents
+                    .values()
+                    .map(|bucket| {
+                        std::mem::size_of::<(usize, BTreeSet<Ortho>)>()
+                            + bucket.iter().map(|o| o.footprint()).sum::<usize>()
+                    })
+                    .sum::<usize>(),
+        }
+    }
+
+    fn footprint(hm: &HashMap<(MultiSet, usize), BTreeSet<Ortho>>) -> usize {
+        hm.iter()
+            .map(|(k, v)| {
+                std::mem::size_of::<((MultiSet, usize), BTreeSet<Ortho>)>()
+                    + k.0.footprint()
+                    + v.iter().map(|o| o.footprint()).sum::<usize>()
+            })
+            .sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Ortho> {
+        self.origin.values().flatten()
     }
 
-    #[allow(dead_code)]
-    pub fn find_by_size_and_hop(&self, size: MultiSet, origin: usize) -> Option<&BTreeSet<Ortho>> {
-        self.hops.get(&(size, origin))
+    fn index_contents(&mut self) {
+        let orthos: Vec<Ortho> = self.iter().cloned().collect();
+        for ortho in orthos {
+            for (_location, word) in ortho.cells() {
+                self.contents
+                    .entry(*word)
+                    .or_default()
+                    .insert(ortho.clone());
+            }
+        }
     }
 
     pub fn new() -> Repo {
         Repo {
             origin: HashMap::default(),
             hops: HashMap::default(),
+            contents: HashMap::default(),
         }
     }
 
-    pub fn unintern(&self, interner: &StringInterner) -> LiteralRepo {
-        LiteralRepo {
-            origin: Self::unintern_hashmap(&self.origin, interner),
-            hops: Self::unintern_hashmap(&self.hops, interner),
-        }
+    pub fn unintern(&self, interner: &StringInterner) -> Result<LiteralRepo> {
+        Ok(LiteralRepo {
+            origin: Self::unintern_hashmap(&self.origin, interner)?,
+            hops: Self::unintern_hashmap(&self.hops, interner)?,
+        })
     }
 
     fn unintern_hashmap(
         hm: &HashMap<(MultiSet, usize), BTreeSet<Ortho>>,
         interner: &StringInterner,
-    ) -> HashMap<(MultiSet, String), BTreeSet<LiteralOrtho>> {
+    ) -> Result<HashMap<(MultiSet, String), BTreeSet<LiteralOrtho>>> {
         hm.iter()
             .map(|(k, v)| {
-                (
-                    (
-                        k.0.clone(),
-                        interner
-                            .resolve(Symbol::try_from_usize(k.1).unwrap())
-                            .unwrap()
-                            .to_string(),
-                    ),
-                    v.iter().map(|v| v.unintern(interner)).collect(),
-                )
+                Ok((
+                    (k.0.clone(), resolve(interner, k.1)?),
+                    v.iter()
+                        .map(|v| v.unintern(interner))
+                        .collect::<Result<_>>()?,
+                ))
             })
             .collect()
     }
 
-    pub fn add(&mut self, ortho: Ortho) {
-        self.origin
-            .entry((ortho.size(), ortho.origin()))
-            .or_insert_with(BTreeSet::default)
-            .insert(ortho.clone());
+    fn validate(&self) -> Result<()> {
+        for ((size, origin), bucket) in self.origin.iter() {
+            for ortho in bucket {
+                if ortho.size() != *size || ortho.origin() != *origin {
+                    return Err(Error::InconsistentRepo(
+                        "ortho filed under the wrong origin".to_string(),
+                    ));
+                }
+                for hop in ortho.hop() {
+                    if !self
+                        .hops
+                        .get(&(size.clone(), *hop))
+                        .is_some_and(|bucket| bucket.contains(ortho))
+                    {
+                        return Err(Error::InconsistentRepo(
+                            "ortho missing from the hop index".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
 
-        for hop in ortho.hop() {
-            self.hops
-                .entry((ortho.size(), *hop))
-                .or_insert_with(BTreeSet::default)
-                .insert(ortho.clone());
+        for ((size, hop), bucket) in self.hops.iter() {
+            for ortho in bucket {
+                if ortho.size() != *size
+                    || !ortho.hop().any(|h| h == hop)
+                    || !self.contains(ortho)?
+                {
+                    return Err(Error::InconsistentRepo(
+                        "ortho filed under the wrong hop".to_string(),
+                    ));
+                }
+            }
         }
+        Ok(())
+    }
+}
+
+impl Default for Repo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RepoStats {
+    pub orthos: usize,
+    pub shapes: BTreeMap<Vec<usize>, usize>,
+    pub origins: usize,
+    pub hop_fan_out: BTreeMap<usize, usize>,
+    pub index_bytes: usize,
+}
+
+impl fmt::Display for RepoStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "orthos: {}", self.orthos)?;
+        writeln!(f, "origins: {}", self.origins)?;
+        writeln!(f, "index bytes: {}", self.index_bytes)?;
+        writeln!(f, "shapes:")?;
+        for (shape, count) in &self.shapes {
+            let shape: Vec<String> = shape.iter().map(|length| length.to_string()).collect();
+            writeln!(f, "  {}: {}", shape.join("x"), count)?;
+        }
+        writeln!(f, "hop fan-out:")?;
+        for (fan_out, count) in &self.hop_fan_out {
+            writeln!(f, "  {}: {}", fan_out, count)?;
+        }
+        Ok(())
     }
 }
 
@@ -97,54 +321,57 @@ pub struct LiteralRepo {
 }
 
 impl LiteralRepo {
-    pub fn intern(&self, interner: &StringInterner) -> Repo {
-        Repo {
-            origin: Self::intern_underlying(&self.origin, interner),
-            hops: Self::intern_underlying(&self.hops, interner),
-        }
+    pub fn intern(&self, interner: &StringInterner) -> Result<Repo> {
+        let mut repo = Repo {
+            origin: Self::intern_underlying(&self.origin, interner)?,
+            hops: Self::intern_underlying(&self.hops, interner)?,
+            contents: HashMap::default(),
+        };
+        repo.validate()?;
+        repo.index_contents();
+        Ok(repo)
     }
 
     fn intern_underlying(
         underlying: &HashMap<(MultiSet, String), BTreeSet<LiteralOrtho>>,
         interner: &StringInterner,
-    ) -> HashMap<(MultiSet, usize), BTreeSet<Ortho>> {
+    ) -> Result<HashMap<(MultiSet, usize), BTreeSet<Ortho>>> {
         underlying
             .iter()
             .map(|(k, v)| {
-                (
-                    (k.0.clone(), interner.get(k.1.clone()).unwrap().to_usize()),
-                    v.iter().map(|v| v.intern(interner)).collect(),
-                )
+                Ok((
+                    (k.0.clone(), lookup(interner, &k.1)?),
+                    v.iter()
+                        .map(|v| v.intern(interner))
+                        .collect::<Result<_>>()?,
+                ))
             })
             .collect()
     }
 
-    pub fn save(&self, mut target: File) {
-        std::io::Write::write_fmt(
-            &mut target,
-            format_args!("{}", serde_yaml::to_string(&self).unwrap()),
-        )
-        .unwrap();
+    pub fn save(&self, mut target: impl Write) -> Result<()> {
+        target.write_all(serde_yaml::to_string(&self)?.as_bytes())?;
+        Ok(())
     }
 
-    pub fn load(mut source: File) -> LiteralRepo {
+    pub fn load(mut source: impl Read) -> Result<LiteralRepo> {
         let mut contents = String::new();
-        source.read_to_string(&mut contents).unwrap();
-        serde_yaml::from_str(&contents).unwrap()
+        source.read_to_string(&mut contents)?;
+        Ok(serde_yaml::from_str(&contents)?)
     }
 
     pub fn merge(&mut self, other: LiteralRepo) {
         for x in other.origin.iter() {
             self.origin
                 .entry(x.0.clone())
-                .or_insert(BTreeSet::default())
+                .or_default()
                 .extend(x.1.iter().cloned());
         }
 
         for x in other.hops.iter() {
             self.hops
                 .entry(x.0.clone())
-                .or_insert(BTreeSet::default())
+                .or_default()
                 .extend(x.1.iter().cloned());
         }
     }
@@ -152,47 +379,79 @@ impl LiteralRepo {
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
-
     use super::*;
+    use crate::Workspace;
+    use std::fs::File;
+    use string_interner::Symbol;
 
     #[test]
     fn it_has_size() {
         let mut repo = Repo::new();
         let ortho = Ortho::new(1, 2, 3, 4);
-        repo.add(ortho.clone());
+        repo.add(ortho.clone()).unwrap();
         let res = repo.len();
         assert_eq!(res, 1);
     }
 
+    #[test]
+    fn it_counts_orthos_rather_than_buckets() {
+        let mut repo = Repo::new();
+        repo.add(Ortho::new(1, 2, 3, 4)).unwrap();
+        repo.add(Ortho::new(1, 5, 3, 6)).unwrap();
+        assert_eq!(repo.len(), 2);
+    }
+
+    #[test]
+    fn it_reports_stats() {
+        let mut repo = Repo::new();
+        repo.add(Ortho::new(1, 2, 3, 4)).unwrap();
+        repo.add(Ortho::new(1, 5, 3, 6)).unwrap();
+        repo.add(Ortho::from_grid(&[2, 3], &[7, 2, 8, 3, 4, 9]).unwrap())
+            .unwrap();
+
+        let stats = repo.stats();
+        assert_eq!(stats.orthos, 3);
+        assert_eq!(stats.origins, 2);
+        assert_eq!(stats.shapes.get(&vec![2, 2]), Some(&2));
+        assert_eq!(stats.shapes.get(&vec![2, 3]), Some(&1));
+        assert_eq!(stats.hop_fan_out.get(&2), Some(&1));
+        assert_eq!(stats.hop_fan_out.get(&1), Some(&4));
+        assert!(stats.index_bytes > 0);
+        assert!(stats.to_string().contains("2x3: 1"));
+    }
+
     #[test]
     fn it_can_be_found_by_size_and_origin() {
         let mut repo = Repo::new();
         let ortho = Ortho::new(1, 2, 3, 4);
-        repo.add(ortho.clone());
+        repo.add(ortho.clone()).unwrap();
         let res = repo
             .find_by_size_and_origin(ortho.size(), ortho.origin())
             .unwrap()
+            .unwrap()
             .iter()
             .next()
+            .cloned()
             .unwrap();
-        assert_eq!(*res, ortho);
+        assert_eq!(res, ortho);
     }
 
     #[test]
     fn it_can_be_found_by_size_and_hop() {
         let mut repo = Repo::new();
         let ortho = Ortho::new(1, 2, 3, 4);
-        repo.add(ortho.clone());
+        repo.add(ortho.clone()).unwrap();
 
         for hop in ortho.hop() {
             let res = repo
                 .find_by_size_and_hop(ortho.size(), *hop)
                 .unwrap()
+                .unwrap()
                 .iter()
                 .next()
+                .cloned()
                 .unwrap();
-            assert_eq!(*res, ortho);
+            assert_eq!(res, ortho);
         }
     }
 
@@ -207,10 +466,10 @@ mod tests {
             interner.get_or_intern("d").to_usize(),
         );
 
-        repo.add(ortho.clone());
+        repo.add(ortho.clone()).unwrap();
 
-        let uninterned = repo.unintern(&interner);
-        let back = uninterned.intern(&mut interner);
+        let uninterned = repo.unintern(&interner).unwrap();
+        let back = uninterned.intern(&interner).unwrap();
 
         assert_eq!(back, repo);
     }
@@ -233,17 +492,37 @@ mod tests {
             interner.get_or_intern("h").to_usize(),
         );
 
-        repo.add(ortho.clone());
+        repo.add(ortho.clone()).unwrap();
 
         let mut target = BTreeSet::default();
         target.insert(ortho.clone());
         target.insert(ortho2.clone());
 
-        let res = repo.set_subract(target);
+        let res = repo.set_subract(target).unwrap();
         assert_eq!(res.len(), 1);
         assert!(res.contains(&ortho2));
     }
 
+    #[test]
+    fn it_subtracts_only_exact_matches() {
+        let mut repo = Repo::new();
+        let ortho = Ortho::new(1, 2, 3, 4);
+        let same_origin = Ortho::new(1, 5, 3, 6);
+        let rotated = Ortho::new(1, 3, 2, 4);
+
+        repo.add(ortho.clone()).unwrap();
+
+        let mut target = BTreeSet::default();
+        target.insert(same_origin.clone());
+        target.insert(rotated.clone());
+
+        let res = repo.set_subract(target).unwrap();
+        assert_eq!(res.len(), 1);
+        assert!(res.contains(&same_origin));
+        assert!(repo.contains(&rotated).unwrap());
+        assert!(!repo.contains(&same_origin).unwrap());
+    }
+
     #[test]
     fn it_can_be_merged() {
         let mut interner = StringInterner::default();
@@ -263,14 +542,14 @@ mod tests {
             interner.get_or_intern("h").to_usize(),
         );
 
-        repo.add(ortho.clone());
-        repo2.add(ortho2.clone());
+        repo.add(ortho).unwrap();
+        repo2.add(ortho2).unwrap();
 
-        let mut literal_repo = repo.unintern(&interner);
-        let literal_repo2 = repo2.unintern(&interner);
+        let mut literal_repo = repo.unintern(&interner).unwrap();
+        let literal_repo2 = repo2.unintern(&interner).unwrap();
 
-        literal_repo.merge(literal_repo2.clone());
-        let res = literal_repo.intern(&mut interner);
+        literal_repo.merge(literal_repo2);
+        let res = literal_repo.intern(&interner).unwrap();
 
         assert_eq!(res.origin.len(), 2);
         assert_eq!(repo.origin.len(), 1);
@@ -282,7 +561,8 @@ mod tests {
 
     #[test]
     fn it_saves_and_loads() {
-        let filename = "temp_two.yaml";
+        let workspace = Workspace::scratch("repo-saves");
+        workspace.prepare().unwrap();
         let mut interner = StringInterner::default();
         let mut repo = Repo::new();
         let ortho = Ortho::new(
@@ -292,17 +572,41 @@ mod tests {
             interner.get_or_intern("d").to_usize(),
         );
 
-        repo.add(ortho.clone());
+        repo.add(ortho.clone()).unwrap();
 
-        let uninterned = repo.unintern(&interner);
+        let uninterned = repo.unintern(&interner).unwrap();
 
-        let f = File::create(filename).unwrap();
-        uninterned.save(f);
+        let f = File::create(&workspace.repo).unwrap();
+        uninterned.save(f).unwrap();
 
-        let f2 = File::open(filename).unwrap();
+        let f2 = File::open(&workspace.repo).unwrap();
 
-        let res = LiteralRepo::load(f2);
-        fs::remove_file(filename).unwrap();
+        let res = LiteralRepo::load(f2).unwrap();
+        workspace.clean();
         assert_eq!(uninterned, res);
     }
+
+    #[test]
+    fn it_rejects_inconsistent_files() {
+        let mut interner = StringInterner::default();
+        let mut repo = Repo::new();
+        let ortho = Ortho::new(
+            interner.get_or_intern("a").to_usize(),
+            interner.get_or_intern("b").to_usize(),
+            interner.get_or_intern("c").to_usize(),
+            interner.get_or_intern("d").to_usize(),
+        );
+        repo.add(ortho.clone()).unwrap();
+        repo.hops.clear();
+
+        let uninterned = repo.unintern(&interner).unwrap();
+        assert!(matches!(
+            uninterned.intern(&interner),
+            Err(Error::InconsistentRepo(_))
+        ));
+        assert!(matches!(
+            uninterned.intern(&StringInterner::default()),
+            Err(Error::UnknownSymbol(_))
+        ));
+    }
 }
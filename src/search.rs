@@ -1,120 +1,303 @@
-use std::fs::File;
-
-use crate::config::LiteralConfig;
+use crate::config::Support;
+use crate::engine::Engine;
+use crate::error::Result;
 use crate::ex_nihilo::create;
-use crate::repo::LiteralRepo;
-use crate::repo::Repo;
-use crate::Config;
-use string_interner::StringInterner;
-
-pub fn search(input: String, config_filename: &str, repo_filename: &str) {
-    if std::path::Path::new(config_filename).exists() {
-        let mut interner = StringInterner::default();
-        let mut literal_config = LiteralConfig::from_raw(input);
-        let config = literal_config.intern(&mut interner);
-        let mut repo = Repo::new();
+use crate::over;
+use crate::repo::RepoStore;
+use crate::up;
+use crate::workspace::Workspace;
+use crate::{Config, Ortho};
+use nohash_hasher::IntSet;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::thread;
 
-        make_atoms(&config, &mut repo);
-
-        let mut literal_repo = repo.unintern(&interner);
-
-        let (old_config, old_repo) = load_from_disk(config_filename, repo_filename);
+pub fn search(input: String, workspace: &Workspace) -> Result<()> {
+    let mut engine = Engine::open(workspace.clone())?;
+    engine.ingest(input)?;
+    engine.save()
+}
 
-        literal_repo.merge(old_repo);
-        literal_config.merge(old_config);
+pub fn make_atoms(
+    config: &Config,
+    support: Support,
+    repo: &mut impl RepoStore,
+    threads: usize,
+) -> Result<Vec<Ortho>> {
+    let words: Vec<usize> = config.iter().cloned().collect();
+    let pending: Vec<Ortho> = repo
+        .orthos()
+        .map(|o| o.map(Cow::into_owned))
+        .collect::<Result<_>>()?;
+    grow(config, support, repo, &words, pending, threads)
+}
 
-        let mut new_interner = StringInterner::default();
+pub fn make_atoms_near(
+    config: &Config,
+    support: Support,
+    repo: &mut impl RepoStore,
+    edges: &BTreeSet<(usize, usize)>,
+    threads: usize,
+) -> Result<Vec<Ortho>> {
+    // a new edge u -> v can only complete an ortho that holds u,
+    // or one whose new cells are reached from a word behind u
+    let mut words = IntSet::default();
+    for (u, _v) in edges {
+        words.insert(*u);
+        if let Some(previous) = config.project_backward_supported(*u, support) {
+            words.extend(previous.iter().cloned());
+        }
+    }
 
-        let current_config = literal_config.intern(&mut new_interner);
-        let mut current_repo = literal_repo.intern(&mut new_interner);
+    // the contents index keeps this independent of the size of the repo
+    let mut near = BTreeSet::default();
+    for word in &words {
+        if let Some(bucket) = repo.find_by_word(*word)? {
+            near.extend(bucket.iter().cloned());
+        }
+    }
+    let pending: Vec<Ortho> = near.into_iter().collect();
+    let words: Vec<usize> = words.into_iter().collect();
+    grow(config, support, repo, &words, pending, threads)
+}
 
-        make_atoms(&current_config, &mut current_repo);
+fn grow(
+    config: &Config,
+    support: Support,
+    repo: &mut impl RepoStore,
+    words: &[usize],
+    mut pending: Vec<Ortho>,
+    threads: usize,
+) -> Result<Vec<Ortho>> {
+    let mut found = vec![];
+    for find in discover(config, support, repo, words, threads)? {
+        repo.add(find.clone())?;
+        pending.push(find.clone());
+        found.push(find);
+    }
 
-        save_to_disk(
-            &mut literal_config,
-            &mut new_interner,
-            &mut current_repo,
-            config_filename,
-            repo_filename,
-        );
-    } else {
-        let mut interner = StringInterner::default();
-        let mut literal_config = LiteralConfig::from_raw(input);
-        let config = literal_config.intern(&mut interner);
+    while let Some(ortho) = pending.pop() {
+        for find in up::create(config, support, repo, &ortho)? {
+            repo.add(find.clone())?;
+            pending.push(find.clone());
+            found.push(find);
+        }
 
-        let mut repo = Repo::new();
-        make_atoms(&config, &mut repo);
-        save_to_disk(
-            &mut literal_config,
-            &mut interner,
-            &mut repo,
-            config_filename,
-            repo_filename,
-        );
+        for find in over::create(config, support, repo, &ortho)? {
+            repo.add(find.clone())?;
+            pending.push(find.clone());
+            found.push(find);
+        }
     }
+    Ok(found)
 }
 
-fn load_from_disk(config_filename: &str, repo_filename: &str) -> (LiteralConfig, LiteralRepo) {
-    let old_config = LiteralConfig::load(File::open(config_filename).unwrap());
-    let old_repo = LiteralRepo::load(File::open(repo_filename).unwrap());
-    (old_config, old_repo)
-}
+fn discover(
+    config: &Config,
+    support: Support,
+    repo: &impl RepoStore,
+    words: &[usize],
+    threads: usize,
+) -> Result<BTreeSet<Ortho>> {
+    // every atom found for a word has that word as its origin,
+    // so the partitions never overlap and the merge order does not matter
+    let mut words = words.to_vec();
+    words.sort_unstable();
+    if threads <= 1 || words.len() < 2 {
+        return atoms(config, support, repo, &words);
+    }
 
-fn save_to_disk(
-    literal_config: &mut LiteralConfig,
-    interner: &mut StringInterner,
-    current_repo: &mut Repo,
-    config_filename: &str,
-    repo_filename: &str,
-) {
-    current_repo
-        .unintern(&interner)
-        .save(File::create(repo_filename).unwrap());
-    literal_config.save(File::create(config_filename).unwrap());
-}
+    let chunk = words.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let workers: Vec<_> = words
+            .chunks(chunk)
+            .map(|part| scope.spawn(move || atoms(config, support, repo, part)))
+            .collect();
 
-fn make_atoms(config: &Config, repo: &mut Repo) {
-    for a in config.iter() {
-        for find in create(config, repo, *a) {
-            repo.add(find);
+        let mut results = BTreeSet::default();
+        for worker in workers {
+            results.append(&mut worker.join().expect("atom discovery panicked")?);
         }
+        Ok(results)
+    })
+}
+
+fn atoms(
+    config: &Config,
+    support: Support,
+    repo: &impl RepoStore,
+    words: &[usize],
+) -> Result<BTreeSet<Ortho>> {
+    let mut results = BTreeSet::default();
+    for a in words {
+        results.append(&mut create(config, support, repo, *a)?);
     }
+    Ok(results)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::LiteralConfig;
+    use crate::repo::Repo;
+    use string_interner::StringInterner;
 
     #[test]
     fn it_advances() {
-        let config_filename = "test_config.yaml";
-        let repo_filename = "test_repo.yaml";
-        search(
-            "a b. c d. a c. b d. i k. j l.".to_string(),
-            config_filename,
-            repo_filename,
+        let workspace = Workspace::scratch("advances");
+        search("a b. c d. a c. b d. i k. j l.".to_string(), &workspace).unwrap();
+        assert_eq!(
+            Engine::open(workspace.clone())
+                .unwrap()
+                .stats()
+                .unwrap()
+                .orthos,
+            1
+        );
+
+        search("e f. g h. e g. f h. i j. k l.".to_string(), &workspace).unwrap();
+        assert_eq!(
+            Engine::open(workspace.clone())
+                .unwrap()
+                .stats()
+                .unwrap()
+                .orthos,
+            3
         );
-        let mut first_interner = StringInterner::default();
-        LiteralConfig::load(File::open(config_filename).unwrap()).intern(&mut first_interner);
-        let first_repo =
-            LiteralRepo::load(File::open(repo_filename).unwrap()).intern(&first_interner);
 
-        assert_eq!(first_repo.len(), 1);
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_discovers_extensions() {
+        let (config, _interner) =
+            Config::from_sentences("a b e g. c d f h. a c. b d. e f. g h.".to_string());
+        let mut repo = Repo::new();
+        make_atoms(&config, Support::default(), &mut repo, 1).unwrap();
+
+        let shapes: BTreeSet<Vec<usize>> = repo
+            .iter()
+            .map(|o| o.dims().values().cloned().collect())
+            .collect();
+        assert_eq!(repo.iter().count(), 6);
+        assert!(shapes.contains(&vec![2, 4]) || shapes.contains(&vec![4, 2]));
+    }
+
+    #[test]
+    fn it_terminates_on_cyclic_graphs() {
+        // a -> b -> a and c -> d -> c would let a fold walk the cycle forever
+        let (config, _interner) = Config::from_sentences("a b a. c d c. a c. b d.".to_string());
+        let mut repo = Repo::new();
+        make_atoms(&config, Support::default(), &mut repo, 1).unwrap();
+
+        assert_eq!(repo.iter().count(), 2);
+    }
+
+    #[test]
+    fn it_keeps_distinct_orthos_with_the_same_origin() {
+        let workspace = Workspace::scratch("same-origin");
+        search("a b. c d. a c. b d.".to_string(), &workspace).unwrap();
+        search("a e. e f. c f.".to_string(), &workspace).unwrap();
 
-        search(
-            "e f. g h. e g. f h. i j. k l.".to_string(),
-            config_filename,
-            repo_filename,
+        assert_eq!(
+            Engine::open(workspace.clone())
+                .unwrap()
+                .stats()
+                .unwrap()
+                .orthos,
+            2
         );
 
-        let mut second_interner = StringInterner::default();
-        LiteralConfig::load(File::open(config_filename).unwrap()).intern(&mut second_interner);
-        let second_repo =
-            LiteralRepo::load(File::open(repo_filename).unwrap()).intern(&second_interner);
+        workspace.clean();
+    }
+
+    fn corpus(seed: u64, words: usize, sentences: usize) -> Vec<String> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize
+        };
+        (0..sentences)
+            .map(|_| {
+                let length = 2 + next() % 3;
+                (0..length)
+                    .map(|_| format!("w{}", next() % words))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_finds_the_same_orthos_incrementally() {
+        for seed in 0..8 {
+            let sentences = corpus(seed, 18, 28);
+            let mut interner = StringInterner::default();
+
+            let mut full_config = Config::new();
+            let mut full_repo = Repo::new();
+            let mut config = Config::new();
+            let mut repo = Repo::new();
+
+            for chunk in sentences.chunks(5) {
+                let text = chunk.join(". ");
+                let edges =
+                    config.merge(LiteralConfig::from_raw(text.clone()).intern(&mut interner));
+                make_atoms_near(&config, Support::default(), &mut repo, &edges, 1).unwrap();
+
+                full_config.merge(LiteralConfig::from_raw(text).intern(&mut interner));
+            }
+            make_atoms(&full_config, Support::default(), &mut full_repo, 1).unwrap();
 
-        assert_eq!(second_repo.len(), 3);
+            assert!(full_repo.len() > 0);
+            assert_eq!(repo, full_repo, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn it_finds_the_same_orthos_on_many_threads() {
+        for seed in 0..4 {
+            let text = corpus(seed, 20, 30).join(". ");
+            let (config, _interner) = Config::from_sentences(text);
+
+            let mut single = Repo::new();
+            make_atoms(&config, Support::default(), &mut single, 1).unwrap();
+            for threads in [2, 3, 8, 64] {
+                let mut many = Repo::new();
+                make_atoms(&config, Support::default(), &mut many, threads).unwrap();
+                assert_eq!(many, single, "seed {} threads {}", seed, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn it_grows_existing_orthos_from_new_edges() {
+        let mut interner = StringInterner::default();
+        let mut config = Config::new();
+        let mut repo = Repo::new();
+
+        let edges = config.merge(
+            LiteralConfig::from_raw("a b. c d. a c. b d. e f. g h. e g. f h.".to_string())
+                .intern(&mut interner),
+        );
+        assert_eq!(
+            make_atoms_near(&config, Support::default(), &mut repo, &edges, 1)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(repo.len(), 2);
+
+        let edges = config.merge(
+            LiteralConfig::from_raw("a e. b f. c g. d h. b x. d y. x y.".to_string())
+                .intern(&mut interner),
+        );
+        make_atoms_near(&config, Support::default(), &mut repo, &edges, 1).unwrap();
 
-        std::fs::remove_file(config_filename).unwrap();
-        std::fs::remove_file(repo_filename).unwrap();
+        let shapes: BTreeSet<Vec<usize>> = repo.iter().map(|o| o.shape()).collect();
+        assert!(shapes.contains(&vec![2, 2, 2]));
+        assert!(shapes.contains(&vec![2, 3]));
     }
 }
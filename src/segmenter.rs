@@ -0,0 +1,260 @@
+use std::collections::BTreeSet;
+
+pub const ABBREVIATIONS: &[&str] = &[
+    "a.m", "p.m", "dr", "mr", "mrs", "ms", "prof", "st", "jr", "sr", "vs", "etc", "e.g", "i.e",
+    "cf", "al", "fig", "no", "vol", "inc", "ltd", "co", "corp", "mt", "jan", "feb", "mar", "apr",
+    "jun", "jul", "aug", "sep", "sept", "oct", "nov", "dec", "u.s", "u.k",
+];
+
+// abbreviations that come before a name rather than at the end of a sentence
+const TITLES: &[&str] = &["dr", "mr", "mrs", "ms", "prof", "mt"];
+
+// company suffixes, which can follow one another as in Co. Ltd.
+const COMPANIES: &[&str] = &["co", "corp", "inc", "ltd"];
+
+// a western terminator ends a sentence only when whitespace or the end of the text
+// follows it, which keeps 3.50, a.m and example.com whole. a period after a known
+// abbreviation only does when the next word is not lowercase or a number, since
+// no., co. and dec. end sentences too, and never after a title or before another
+// company suffix. a single capital
+// initial holds it only inside a name: before another initial, or when the word
+// before it is not lowercase. an ellipsis or a quoted end only ends a sentence
+// when the next word is not lowercase
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segmenter {
+    abbreviations: BTreeSet<String>,
+}
+
+impl Default for Segmenter {
+    fn default() -> Self {
+        Segmenter::new(ABBREVIATIONS)
+    }
+}
+
+impl Segmenter {
+    pub fn new(abbreviations: impl IntoIterator<Item = impl AsRef<str>>) -> Segmenter {
+        Segmenter {
+            abbreviations: abbreviations
+                .into_iter()
+                .map(|word| word.as_ref().trim().trim_end_matches('.').to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn split<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let offset = |i: usize| chars.get(i).map_or(text.len(), |(offset, _c)| *offset);
+        let mut sentences = vec![];
+        let mut start = 0;
+        let mut i = 0;
+        while i < chars.len() {
+            if !is_terminator(chars[i].1) {
+                i += 1;
+                continue;
+            }
+            let run_start = i;
+            while i < chars.len() && is_terminator(chars[i].1) {
+                i += 1;
+            }
+            let run_end = i;
+            while i < chars.len() && is_closing(chars[i].1) {
+                i += 1;
+            }
+            if self.is_boundary(&chars, run_start, run_end, i) {
+                sentences.push(&text[offset(start)..offset(i)]);
+                start = i;
+            }
+        }
+        sentences.push(&text[offset(start)..]);
+        sentences
+            .into_iter()
+            .map(str::trim)
+            .filter(|sentence| !sentence.is_empty())
+            .collect()
+    }
+
+    fn is_boundary(
+        &self,
+        chars: &[(usize, char)],
+        run_start: usize,
+        run_end: usize,
+        after: usize,
+    ) -> bool {
+        let run: String = chars[run_start..run_end]
+            .iter()
+            .map(|(_offset, c)| c)
+            .collect();
+        let mut rest = chars[after..].iter().map(|(_offset, c)| *c);
+        match rest.next() {
+            None => return true,
+            // cjk text puts no space after a full stop
+            Some(_) if run.contains(['。', '！', '？']) => return true,
+            Some(c) if !c.is_whitespace() => return false,
+            Some(_) => {}
+        }
+
+        let next: Vec<char> = rest
+            .skip_while(|c| c.is_whitespace() || is_opening(*c))
+            .take_while(|c| !c.is_whitespace())
+            .collect();
+        if run == "." && self.holds(&chars[..run_start], &next) {
+            return false;
+        }
+        let ellipsis = run.contains('…') || run.matches('.').count() > 1;
+        let quoted = after > run_end;
+        if ellipsis || quoted {
+            return !next.first().is_some_and(|c| c.is_lowercase());
+        }
+        true
+    }
+
+    fn holds(&self, before: &[(usize, char)], next: &[char]) -> bool {
+        let (start, word) = last_word(before);
+        let lower = word.to_lowercase();
+        let continues = next
+            .first()
+            .is_some_and(|c| c.is_lowercase() || c.is_numeric());
+        if self.abbreviations.contains(&lower) {
+            let next: String = next.iter().collect();
+            let next = next.trim_end_matches(['.', ',']).to_lowercase();
+            return continues
+                || TITLES.contains(&lower.as_str())
+                || (COMPANIES.contains(&lower.as_str()) && COMPANIES.contains(&next.as_str()));
+        }
+        if is_initial(&word.chars().collect::<Vec<char>>()) {
+            let (_start, previous) = last_word(&before[..start]);
+            return continues
+                || is_initial(next)
+                || !previous.chars().next().is_some_and(char::is_lowercase);
+        }
+        false
+    }
+}
+
+// the word the text ends on, skipping the space after it, and where it starts
+fn last_word(before: &[(usize, char)]) -> (usize, String) {
+    let end = before
+        .iter()
+        .rposition(|(_offset, c)| !c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let start = before[..end]
+        .iter()
+        .rposition(|(_offset, c)| c.is_whitespace() || is_opening(*c))
+        .map_or(0, |i| i + 1);
+    let word = before[start..end].iter().map(|(_offset, c)| *c).collect();
+    (start, word)
+}
+
+// a capital letter on its own, or with the period after it
+fn is_initial(word: &[char]) -> bool {
+    match word {
+        [c] | [c, '.'] => c.is_uppercase(),
+        _ => false,
+    }
+}
+
+fn is_terminator(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | '…' | '。' | '！' | '？')
+}
+
+fn is_closing(c: char) -> bool {
+    matches!(c, '"' | '\'' | '”' | '’' | '»' | ')' | ']')
+}
+
+fn is_opening(c: char) -> bool {
+    matches!(c, '"' | '\'' | '“' | '‘' | '«' | '(' | '[')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str) -> Vec<&str> {
+        Segmenter::default().split(text)
+    }
+
+    #[test]
+    fn it_keeps_abbreviations_and_decimals_inside_sentences() {
+        assert_eq!(
+            split("Dr. Smith paid 3.50 at 10 a.m. on Main St. today. He left!"),
+            vec![
+                "Dr. Smith paid 3.50 at 10 a.m. on Main St. today.",
+                "He left!"
+            ]
+        );
+        assert_eq!(
+            split("J. R. Tolkien wrote it, i.e. the book. See example.com."),
+            vec!["J. R. Tolkien wrote it, i.e. the book.", "See example.com."]
+        );
+    }
+
+    #[test]
+    fn it_ends_sentences_on_abbreviations_and_initials_before_a_new_one() {
+        assert_eq!(
+            split("The answer was no. She left. He got an A. Then he left. Smith & Co. Ltd. sued."),
+            vec![
+                "The answer was no.",
+                "She left.",
+                "He got an A.",
+                "Then he left.",
+                "Smith & Co. Ltd. sued."
+            ]
+        );
+        assert_eq!(
+            split("They sued Smith & Co. Ltd. Then they won. We see A . Then we go."),
+            vec![
+                "They sued Smith & Co. Ltd.",
+                "Then they won.",
+                "We see A .",
+                "Then we go."
+            ]
+        );
+        assert_eq!(
+            split("Read No. 5 by J. R. R. Tolkien on 3 Dec. 1937. Mr. Brown agreed."),
+            vec![
+                "Read No. 5 by J. R. R. Tolkien on 3 Dec. 1937.",
+                "Mr. Brown agreed."
+            ]
+        );
+    }
+
+    #[test]
+    fn it_splits_ellipses_before_a_new_sentence_only() {
+        assert_eq!(
+            split("Well... maybe not. Wait… The end... "),
+            vec!["Well... maybe not.", "Wait…", "The end..."]
+        );
+    }
+
+    #[test]
+    fn it_keeps_closing_quotes_with_their_sentence() {
+        assert_eq!(
+            split("He said \"stop.\" Then he ran. \"Why?\" she asked. (It rained.) Fine?!"),
+            vec![
+                "He said \"stop.\"",
+                "Then he ran.",
+                "\"Why?\" she asked.",
+                "(It rained.)",
+                "Fine?!"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_takes_a_custom_abbreviation_list() {
+        let segmenter = Segmenter::new(["approx.", "Dept"]);
+        assert_eq!(
+            segmenter.split("Approx. ten people. Dept. of Labor. Dr. Who."),
+            vec!["Approx. ten people.", "Dept. of Labor.", "Dr.", "Who."]
+        );
+    }
+
+    #[test]
+    fn it_splits_cjk_terminators() {
+        assert_eq!(
+            split("我爱北京。天安门很大！"),
+            vec!["我爱北京。", "天安门很大！"]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use crate::disk::DiskRepo;
+use crate::error::{Error, Result};
+use crate::ortho::{MultiSet, Ortho};
+use crate::repo::{LiteralRepo, Repo, RepoStats, RepoStore};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use string_interner::StringInterner;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Memory,
+    Sled,
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Backend> {
+        match name {
+            "memory" => Ok(Backend::Memory),
+            "sled" => Ok(Backend::Sled),
+            other => Err(Error::Format(format!("unknown store {}", other))),
+        }
+    }
+}
+
+// the orthos of a workspace, either in the snapshot or in a sled store
+// beside it
+pub enum Store {
+    Memory(Repo),
+    Disk(DiskRepo),
+}
+
+impl Store {
+    pub fn backend(&self) -> Backend {
+        match self {
+            Store::Memory(_) => Backend::Memory,
+            Store::Disk(_) => Backend::Sled,
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match self {
+            Store::Memory(_) => Ok(()),
+            Store::Disk(disk) => disk.flush(),
+        }
+    }
+
+    pub fn stats(&self) -> Result<RepoStats> {
+        match self {
+            Store::Memory(repo) => Ok(repo.stats()),
+            Store::Disk(disk) => disk.stats(),
+        }
+    }
+
+    pub fn unintern(&self, interner: &StringInterner) -> Result<LiteralRepo> {
+        match self {
+            Store::Memory(repo) => repo.unintern(interner),
+            Store::Disk(disk) => {
+                let mut repo = Repo::new();
+                for ortho in disk.orthos() {
+                    repo.add(ortho?.into_owned())?;
+                }
+                repo.unintern(interner)
+            }
+        }
+    }
+}
+
+impl RepoStore for Store {
+    fn add(&mut self, ortho: Ortho) -> Result<()> {
+        match self {
+            Store::Memory(repo) => repo.add(ortho),
+            Store::Disk(disk) => disk.add(ortho),
+        }
+    }
+
+    fn contains(&self, ortho: &Ortho) -> Result<bool> {
+        match self {
+            Store::Memory(repo) => repo.contains(ortho),
+            Store::Disk(disk) => disk.contains(ortho),
+        }
+    }
+
+    fn find_by_size_and_origin(
+        &self,
+        size: MultiSet,
+        origin: usize,
+    ) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>> {
+        match self {
+            Store::Memory(repo) => repo.find_by_size_and_origin(size, origin),
+            Store::Disk(disk) => disk.find_by_size_and_origin(size, origin),
+        }
+    }
+
+    fn find_by_size_and_hop(
+        &self,
+        size: MultiSet,
+        hop: usize,
+    ) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>> {
+        match self {
+            Store::Memory(repo) => repo.find_by_size_and_hop(size, hop),
+            Store::Disk(disk) => disk.find_by_size_and_hop(size, hop),
+        }
+    }
+
+    fn find_by_word(&self, word: usize) -> Result<Option<Cow<'_, BTreeSet<Ortho>>>> {
+        match self {
+            Store::Memory(repo) => repo.find_by_word(word),
+            Store::Disk(disk) => disk.find_by_word(word),
+        }
+    }
+
+    fn orthos(&self) -> Box<dyn Iterator<Item = Result<Cow<'_, Ortho>>> + '_> {
+        match self {
+            Store::Memory(repo) => repo.orthos(),
+            Store::Disk(disk) => disk.orthos(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Store::Memory(repo) => repo.len(),
+            Store::Disk(disk) => disk.len(),
+        }
+    }
+}
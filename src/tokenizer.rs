@@ -0,0 +1,350 @@
+use crate::error::{Error, Result};
+use crate::segmenter::Segmenter;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub trait Tokenizer {
+    fn sentences<'a>(&self, text: &'a str) -> Vec<&'a str>;
+
+    fn words(&self, sentence: &str) -> Vec<String>;
+
+    fn tokenize(&self, text: &str) -> Vec<Vec<String>> {
+        self.sentences(text)
+            .into_iter()
+            .map(|sentence| self.words(sentence))
+            .collect()
+    }
+}
+
+pub const NAMES: &[&str] = &["classic", "lines", "unicode", "prose"];
+
+// options follow a colon, e.g. unicode:nfkc,keep-case; only prose takes a
+// list of abbreviations
+pub fn named(name: &str, abbreviations: Option<Vec<String>>) -> Result<Box<dyn Tokenizer>> {
+    let (base, options) = name.split_once(':').unwrap_or((name, ""));
+    match (base, abbreviations) {
+        ("prose", abbreviations) => Ok(Box::new(Prose {
+            segmenter: abbreviations.map_or_else(Segmenter::default, Segmenter::new),
+            words: Unicode::with_options(options)?,
+        })),
+        ("unicode", None) => Ok(Box::new(Unicode::with_options(options)?)),
+        (_, Some(_)) => Err(Error::Format(format!(
+            "tokenizer {} takes no abbreviations",
+            name
+        ))),
+        ("classic", None) if name == base => Ok(Box::new(Classic)),
+        ("lines", None) if name == base => Ok(Box::new(Lines)),
+        _ => Err(Error::Format(format!(
+            "unknown tokenizer {} (expected one of {})",
+            name,
+            NAMES.join(", ")
+        ))),
+    }
+}
+
+// sentences end at . ! or ?; a little punctuation is dropped and the rest
+// is lowercased and split on ascii whitespace
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Classic;
+
+impl Tokenizer for Classic {
+    fn sentences<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        text.split(['.', '!', '?'])
+            .filter(|x| !x.is_empty())
+            .collect()
+    }
+
+    fn words(&self, sentence: &str) -> Vec<String> {
+        sentence
+            .replace(";", "")
+            .replace("\'", "")
+            .replace(":", "")
+            .replace(",", "")
+            .to_lowercase()
+            .split_ascii_whitespace()
+            .map(|x| x.to_string())
+            .collect()
+    }
+}
+
+// for corpora that are already tokenized: one sentence per line and tokens
+// taken verbatim
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Lines;
+
+impl Tokenizer for Lines {
+    fn sentences<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect()
+    }
+
+    fn words(&self, sentence: &str) -> Vec<String> {
+        sentence.split_whitespace().map(|x| x.to_string()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    None,
+    Nfc,
+    Nfkc,
+}
+
+// sentences and words follow the unicode segmentation rules (uax #29), so
+// punctuation and spaces of any script separate words and cjk text splits
+// per ideograph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unicode {
+    pub fold_case: bool,
+    pub normalization: Normalization,
+}
+
+impl Default for Unicode {
+    fn default() -> Self {
+        Unicode {
+            fold_case: true,
+            normalization: Normalization::Nfc,
+        }
+    }
+}
+
+impl Unicode {
+    pub fn with_options(options: &str) -> Result<Unicode> {
+        let mut unicode = Unicode::default();
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            match option {
+                "nfc" => unicode.normalization = Normalization::Nfc,
+                "nfkc" => unicode.normalization = Normalization::Nfkc,
+                "no-normalization" => unicode.normalization = Normalization::None,
+                "fold-case" => unicode.fold_case = true,
+                "keep-case" => unicode.fold_case = false,
+                other => {
+                    return Err(Error::Format(format!(
+                        "unknown unicode tokenizer option {}",
+                        other
+                    )))
+                }
+            }
+        }
+        Ok(unicode)
+    }
+
+    fn normalize(&self, word: &str) -> String {
+        match self.normalization {
+            Normalization::None => word.to_string(),
+            Normalization::Nfc => word.nfc().collect(),
+            Normalization::Nfkc => word.nfkc().collect(),
+        }
+    }
+}
+
+impl Tokenizer for Unicode {
+    fn sentences<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        text.unicode_sentences().collect()
+    }
+
+    // normalizing before segmenting lets compatibility forms like ℡ come
+    // through as letters; lowercasing can undo composition, so words are
+    // normalized again after folding
+    fn words(&self, sentence: &str) -> Vec<String> {
+        self.normalize(sentence)
+            .unicode_words()
+            .map(|word| {
+                if self.fold_case {
+                    self.normalize(&word.to_lowercase())
+                } else {
+                    word.to_string()
+                }
+            })
+            .collect()
+    }
+}
+
+// the segmenter keeps abbreviations, decimals and quoted ends inside their
+// sentence; words come out as the unicode tokenizer splits them
+#[derive(Debug, Clone, Default)]
+pub struct Prose {
+    pub segmenter: Segmenter,
+    pub words: Unicode,
+}
+
+impl Tokenizer for Prose {
+    fn sentences<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        self.segmenter.split(text)
+    }
+
+    fn words(&self, sentence: &str) -> Vec<String> {
+        self.words.words(sentence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LiteralConfig;
+    use crate::ortho::resolve;
+    use std::collections::BTreeSet;
+    use string_interner::StringInterner;
+
+    fn words(sentences: &[&[&str]]) -> Vec<Vec<String>> {
+        sentences
+            .iter()
+            .map(|sentence| sentence.iter().map(|word| word.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn it_splits_like_clean_sentences() {
+        assert_eq!(
+            Classic.tokenize("The cat's mat; a dog, a bird!\nWhy: not?  Go.."),
+            words(&[
+                &["the", "cats", "mat", "a", "dog", "a", "bird"],
+                &["why", "not"],
+                &["go"],
+            ])
+        );
+    }
+
+    #[test]
+    fn it_takes_lines_verbatim() {
+        assert_eq!(
+            Lines.tokenize("The cat's mat .\n\n  3.14 is  pi\n"),
+            words(&[&["The", "cat's", "mat", "."], &["3.14", "is", "pi"]])
+        );
+    }
+
+    #[test]
+    fn it_splits_accented_text() {
+        assert_eq!(
+            Unicode::default().tokenize("Élan vital, «café» crème! Ça va?"),
+            words(&[&["élan", "vital", "café", "crème"], &["ça", "va"]])
+        );
+    }
+
+    #[test]
+    fn it_composes_decomposed_accents() {
+        let decomposed = "cafe\u{301} E\u{301}cole";
+        assert_eq!(
+            Unicode::default().tokenize(decomposed),
+            words(&[&["café", "école"]])
+        );
+        let raw = Unicode {
+            normalization: Normalization::None,
+            ..Unicode::default()
+        };
+        assert_eq!(
+            raw.tokenize(decomposed),
+            words(&[&["cafe\u{301}", "e\u{301}cole"]])
+        );
+    }
+
+    #[test]
+    fn it_splits_cjk_text() {
+        assert_eq!(
+            Unicode::default().tokenize("我爱北京。天安门很大！"),
+            words(&[&["我", "爱", "北", "京"], &["天", "安", "门", "很", "大"]])
+        );
+    }
+
+    #[test]
+    fn it_splits_mixed_scripts_on_any_punctuation() {
+        assert_eq!(
+            Unicode::default()
+                .tokenize("Tokyo (東京) \u{2014} the \u{201c}big\u{201d}\u{a0}city. Москва тоже."),
+            words(&[
+                &["tokyo", "東", "京", "the", "big", "city"],
+                &["москва", "тоже"],
+            ])
+        );
+    }
+
+    #[test]
+    fn it_applies_compatibility_normalization() {
+        let text = "The \u{fb01}ne ＡＢＣ \u{2121}";
+        assert_eq!(
+            Unicode::with_options("nfkc").unwrap().tokenize(text),
+            words(&[&["the", "fine", "abc", "tel"]])
+        );
+        assert_eq!(
+            Unicode::with_options("nfkc,keep-case")
+                .unwrap()
+                .tokenize(text),
+            words(&[&["The", "fine", "ABC", "TEL"]])
+        );
+        assert_eq!(
+            Unicode::default().tokenize(text),
+            words(&[&["the", "\u{fb01}ne", "ａｂｃ"]])
+        );
+    }
+
+    // one sentence per entry, split by hand
+    const CORPUS: &[&str] = &[
+        "Dr. Smith paid 3.50 at 10 a.m. on Main St. yesterday.",
+        "Mrs. Jones said \"the shop opens at 9.30.\"",
+        "Prices rose 2.5 percent vs. last year, i.e. more than expected.",
+        "Well... nobody saw that coming!",
+        "\"Is it true?\" asked Prof. Brown.",
+        "The U.S. team won the cup in 1998.",
+        "J. R. R. Tolkien wrote it in 1937.",
+        "Call me at 5 p.m. tomorrow or e-mail me at joe@example.com.",
+        "The answer was no.",
+        "She left.",
+        "He got an A.",
+        "Then he left.",
+    ];
+
+    fn edges(sentences: Vec<Vec<String>>) -> BTreeSet<(String, String)> {
+        let mut interner = StringInterner::default();
+        let config = LiteralConfig::new(sentences).intern(&mut interner);
+        config
+            .edges()
+            .map(|(from, to)| {
+                (
+                    resolve(&interner, from).unwrap(),
+                    resolve(&interner, to).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    // edges the tokenizer adds that the hand split does not have, and true
+    // edges it loses
+    fn errors(tokenizer: &dyn Tokenizer) -> (usize, usize) {
+        let gold = edges(
+            CORPUS
+                .iter()
+                .map(|sentence| tokenizer.words(sentence))
+                .collect(),
+        );
+        let found = edges(tokenizer.tokenize(&CORPUS.join(" ")));
+        (
+            found.difference(&gold).count(),
+            gold.difference(&found).count(),
+        )
+    }
+
+    #[test]
+    fn it_avoids_spurious_edges_on_a_corpus() {
+        assert_eq!(errors(&Classic), (24, 36));
+        assert_eq!(errors(&Unicode::default()), (0, 7));
+        assert_eq!(errors(&Prose::default()), (0, 0));
+    }
+
+    #[test]
+    fn it_looks_tokenizers_up_by_name() {
+        for name in NAMES {
+            assert!(named(name, None).is_ok());
+        }
+        assert_eq!(
+            named("lines", None).unwrap().tokenize("a b"),
+            words(&[&["a", "b"]])
+        );
+        assert!(matches!(named("nltk", None), Err(Error::Format(_))));
+        assert!(named("unicode:nfkc,keep-case", None).is_ok());
+        assert!(named("unicode:nfd", None).is_err());
+        assert!(named("lines:nfc", None).is_err());
+        assert!(named("prose:nfkc", Some(vec!["approx".to_string()])).is_ok());
+        assert!(named("unicode", Some(vec!["approx".to_string()])).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::config::Support;
+use crate::error::Result;
+use crate::repo::RepoStore;
+use crate::{Config, Ortho};
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+pub fn create(
+    config: &Config,
+    support: Support,
+    repo: &impl RepoStore,
+    ortho: &Ortho,
+) -> Result<BTreeSet<Ortho>> {
+    let mut results = BTreeSet::default();
+    // bottom: a -> b, a -> c
+    // top:    e -> f, e -> g
+    // a -> e, b -> f, c -> g, d -> h
+
+    if let Some(potential_origins) = config.project_forward_supported(ortho.origin(), support) {
+        for origin in potential_origins.iter() {
+            if let Some(partners) = repo.find_by_size_and_origin(ortho.size(), *origin)? {
+                for partner in partners.iter() {
+                    results.extend(attempt(config, support, ortho, partner));
+                }
+            }
+        }
+    }
+
+    if let Some(axis) = ortho.axes().first() {
+        if let Some(potential_hops) = config.project_backward_supported(*axis, support) {
+            for hop in potential_hops.iter() {
+                if let Some(partners) = repo.find_by_size_and_hop(ortho.size(), *hop)? {
+                    for partner in partners.iter() {
+                        results.extend(attempt(config, support, partner, ortho));
+                    }
+                }
+            }
+        }
+    }
+    repo.set_subract(results)
+}
+
+fn attempt(config: &Config, support: Support, bottom: &Ortho, top: &Ortho) -> BTreeSet<Ortho> {
+    let mut results = BTreeSet::default();
+    if top.cells().any(|(_location, name)| bottom.contains(*name)) {
+        return results;
+    }
+
+    let mut mappings = vec![];
+    map_axes(
+        config,
+        support,
+        &bottom.axes(),
+        &bottom.dims(),
+        &top.dims(),
+        BTreeMap::default(),
+        &mut mappings,
+    );
+
+    for mapping in mappings {
+        if connected(config, support, bottom, top, &mapping) {
+            if let Some(found) = bottom.zip_up(top, &mapping) {
+                results.insert(found);
+            }
+        }
+    }
+    results
+}
+
+fn map_axes(
+    config: &Config,
+    support: Support,
+    remaining: &[usize],
+    bottom_dims: &BTreeMap<usize, usize>,
+    top_dims: &BTreeMap<usize, usize>,
+    mapping: BTreeMap<usize, usize>,
+    mappings: &mut Vec<BTreeMap<usize, usize>>,
+) {
+    match remaining.split_first() {
+        None => mappings.push(mapping),
+        Some((axis, rest)) => {
+            if let Some(potential_axes) = config.project_forward_supported(*axis, support) {
+                for (top_axis, length) in top_dims {
+                    if potential_axes.contains(top_axis)
+                        && bottom_dims.get(axis) == Some(length)
+                        && !mapping.values().any(|used| used == top_axis)
+                    {
+                        let mut next = mapping.clone();
+                        next.insert(*axis, *top_axis);
+                        map_axes(config, support, rest, bottom_dims, top_dims, next, mappings);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn connected(
+    config: &Config,
+    support: Support,
+    bottom: &Ortho,
+    top: &Ortho,
+    mapping: &BTreeMap<usize, usize>,
+) -> bool {
+    bottom.cells().all(|(location, word)| {
+        match (
+            config.project_forward_supported(*word, support),
+            top.get(&location.rename(mapping)),
+        ) {
+            (Some(potential), Some(target)) => potential.contains(&target),
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::Repo;
+    use string_interner::{StringInterner, Symbol};
+
+    fn square(interner: &StringInterner, words: [&str; 4]) -> Ortho {
+        let [a, b, c, d] = words.map(|word| interner.get(word).unwrap().to_usize());
+        Ortho::new(a, b, c, d)
+    }
+
+    #[test]
+    fn it_joins_two_squares_into_a_cube() {
+        let (config, interner) = Config::from_sentences(
+            "a b. c d. a c. b d. e f. g h. e g. f h. a e. b f. c g. d h.".to_string(),
+        );
+        let mut repo = Repo::new();
+        let bottom = square(&interner, ["a", "b", "c", "d"]);
+        let top = square(&interner, ["e", "f", "g", "h"]);
+        repo.add(bottom.clone()).unwrap();
+        repo.add(top.clone()).unwrap();
+
+        let from_bottom = create(&config, Support::default(), &repo, &bottom).unwrap();
+        let from_top = create(&config, Support::default(), &repo, &top).unwrap();
+
+        assert_eq!(from_bottom.len(), 1);
+        assert_eq!(from_bottom, from_top);
+
+        let cube = from_bottom.iter().next().unwrap();
+        assert_eq!(cube.origin(), interner.get("a").unwrap().to_usize());
+        assert_eq!(cube.cells().count(), 8);
+    }
+
+    #[test]
+    fn it_requires_every_position_to_be_connected() {
+        let (config, interner) = Config::from_sentences(
+            "a b. c d. a c. b d. e f. g h. e g. f h. a e. b f. c g.".to_string(),
+        );
+        let mut repo = Repo::new();
+        let bottom = square(&interner, ["a", "b", "c", "d"]);
+        repo.add(bottom.clone()).unwrap();
+        repo.add(square(&interner, ["e", "f", "g", "h"])).unwrap();
+
+        assert!(create(&config, Support::default(), &repo, &bottom)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::error::{Error, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    pub config: PathBuf,
+    pub repo: PathBuf,
+    pub state: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    Wait,
+    Fail,
+}
+
+#[derive(Debug)]
+pub struct Lock {
+    _file: File,
+}
+
+impl Workspace {
+    pub fn new(
+        config: impl Into<PathBuf>,
+        repo: impl Into<PathBuf>,
+        state: impl Into<PathBuf>,
+    ) -> Workspace {
+        Workspace {
+            config: config.into(),
+            repo: repo.into(),
+            state: state.into(),
+        }
+    }
+
+    pub fn in_dir(dir: impl AsRef<Path>) -> Workspace {
+        let dir = dir.as_ref();
+        Workspace::new(
+            dir.join("config.yaml"),
+            dir.join("repo.yaml"),
+            dir.join("state.bin"),
+        )
+    }
+
+    pub fn exists(&self) -> bool {
+        self.state.exists() || self.journal_path().exists() || self.config.exists()
+    }
+
+    pub fn prepare(&self) -> std::io::Result<()> {
+        for path in [&self.config, &self.repo, &self.state] {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn reset(&self) -> std::io::Result<()> {
+        for path in [
+            self.config.clone(),
+            self.repo.clone(),
+            self.state.clone(),
+            self.journal_path(),
+        ] {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        if self.store_path().exists() {
+            std::fs::remove_dir_all(self.store_path())?;
+        }
+        Ok(())
+    }
+
+    // only there for workspaces that keep their orthos in sled
+    pub fn store_path(&self) -> PathBuf {
+        self.state.with_extension("sled")
+    }
+
+    // one journal per state file, so it is covered by that state's lock
+    pub fn journal_path(&self) -> PathBuf {
+        self.state.with_extension("journal")
+    }
+
+    pub fn lock_path(&self) -> PathBuf {
+        self.state.with_extension("lock")
+    }
+
+    // advisory, so it only keeps out other model-fold runs; released when dropped.
+    // it creates no directories, so locking a workspace that was never prepared fails
+    pub fn lock(&self, mode: LockMode) -> Result<Lock> {
+        let path = self.lock_path();
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        match mode {
+            LockMode::Wait => file.lock()?,
+            LockMode::Fail => match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => return Err(Error::Locked(path)),
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            },
+        }
+        Ok(Lock { _file: file })
+    }
+
+    #[cfg(test)]
+    pub fn scratch(name: &str) -> Workspace {
+        let dir = std::env::temp_dir().join(format!("model-fold-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Workspace::in_dir(dir)
+    }
+
+    #[cfg(test)]
+    pub fn clean(&self) {
+        if let Some(parent) = self.config.parent() {
+            let _ = std::fs::remove_dir_all(parent);
+        }
+    }
+}
+
+// readers see either the old file or the new one, never a partial write
+pub fn replace(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+
+    let mut target = BufWriter::new(File::create(&temporary)?);
+    write(&mut target)?;
+    target
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    fs::rename(&temporary, path)?;
+    sync_parent(path)
+}
+
+// a new or renamed file only survives a crash once its directory entry does
+pub fn sync_parent(path: &Path) -> Result<()> {
+    if cfg!(unix) {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        Workspace::in_dir(".")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_places_files_in_a_directory() {
+        let workspace = Workspace::in_dir("state");
+        assert_eq!(workspace.config, Path::new("state").join("config.yaml"));
+        assert_eq!(workspace.repo, Path::new("state").join("repo.yaml"));
+        assert_eq!(workspace.state, Path::new("state").join("state.bin"));
+        assert_eq!(
+            workspace.journal_path(),
+            Path::new("state").join("state.journal")
+        );
+    }
+
+    #[test]
+    fn it_prepares_missing_directories() {
+        let workspace = Workspace::scratch("prepare");
+        assert!(!workspace.exists());
+        workspace.prepare().unwrap();
+        assert!(workspace.config.parent().unwrap().is_dir());
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_resets() {
+        let workspace = Workspace::scratch("reset");
+        workspace.prepare().unwrap();
+        std::fs::write(&workspace.config, "").unwrap();
+        std::fs::write(&workspace.repo, "").unwrap();
+        std::fs::write(&workspace.state, "").unwrap();
+        std::fs::write(workspace.journal_path(), "").unwrap();
+        assert!(workspace.exists());
+
+        workspace.reset().unwrap();
+        assert!(!workspace.exists());
+        assert!(!workspace.repo.exists());
+        assert!(!workspace.state.exists());
+        assert!(!workspace.journal_path().exists());
+        workspace.reset().unwrap();
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_replaces_files_whole() {
+        let workspace = Workspace::scratch("replace");
+        workspace.prepare().unwrap();
+        fs::write(&workspace.state, "old").unwrap();
+
+        let res = replace(&workspace.state, |target| {
+            std::io::Write::write_all(target, b"half")?;
+            Err(Error::Format("interrupted".to_string()))
+        });
+        assert!(res.is_err());
+        assert_eq!(fs::read_to_string(&workspace.state).unwrap(), "old");
+
+        replace(&workspace.state, |target| {
+            Ok(std::io::Write::write_all(target, b"new")?)
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&workspace.state).unwrap(), "new");
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_fails_fast_on_a_held_lock() {
+        let workspace = Workspace::scratch("lock");
+        workspace.prepare().unwrap();
+        let held = workspace.lock(LockMode::Fail).unwrap();
+        assert!(matches!(
+            workspace.lock(LockMode::Fail),
+            Err(Error::Locked(_))
+        ));
+
+        drop(held);
+        workspace.lock(LockMode::Fail).unwrap();
+        workspace.clean();
+    }
+
+    #[test]
+    fn it_locks_without_creating_directories() {
+        let workspace = Workspace::scratch("lock-missing");
+        assert!(workspace.lock(LockMode::Fail).is_err());
+        assert!(!workspace.lock_path().exists());
+        assert!(!workspace.state.parent().unwrap().exists());
+    }
+}